@@ -0,0 +1,243 @@
+#import bevy_sprite::mesh2d_vertex_output MeshVertexOutput
+
+// Must match `BoardPalette` in board_material.rs
+struct BoardPalette {
+    alive: vec4<f32>,
+    dead: vec4<f32>,
+    newborn: vec4<f32>,
+    young: vec4<f32>,
+    mature: vec4<f32>,
+    trail: vec4<f32>,
+    few_neighbours: vec4<f32>,
+    many_neighbours: vec4<f32>,
+    dying: vec4<f32>,
+    born: vec4<f32>,
+    survived: vec4<f32>,
+    color_mode: u32,
+    trail_length: u32,
+    progress: f32,
+    shape: u32,
+    gap: f32,
+    overview: u32,
+    by_state: u32,
+    lattice: u32,
+};
+
+@group(1) @binding(0)
+var<uniform> palette: BoardPalette;
+@group(1) @binding(1)
+var cells: texture_2d<f32>;
+@group(1) @binding(2)
+var previous: texture_2d<f32>;
+@group(1) @binding(3)
+var state_colors: texture_2d<f32>;
+
+// Same order as `ColorMode`, 0 is the classic two colors
+const AGE: u32 = 1u;
+const NEIGHBOURS: u32 = 2u;
+const DYING: u32 = 3u;
+const BIRTH_SURVIVAL: u32 = 4u;
+
+// Same order as `CellShape`, 0 draws the texel as it is
+const TEXEL: u32 = 0u;
+const ROUNDED_SQUARE: u32 = 2u;
+const CIRCLE: u32 = 3u;
+
+// Same order as `Lattice`
+const SQUARE: u32 = 0u;
+const HEXAGONAL: u32 = 1u;
+
+// Radius of the circle inside a triangular cell, `TRIANGLE_INRADIUS` in lattice.rs
+const TRIANGLE_INRADIUS: f32 = 0.41421356;
+
+// Cells of the neighbouring tiles around the cells of a tile, see `APRON` in tiles.rs
+const APRON: i32 = 1;
+
+// Generations alive until a cell looks young, mature and old
+const AGE_YOUNG: f32 = 4.0;
+const AGE_MATURE: f32 = 16.0;
+const AGE_OLD: f32 = 64.0;
+
+// Bits of a cell, see `palette::encode_cell`
+const ALIVE_BIT: u32 = 128u;
+const LEVEL_MASK: u32 = 127u;
+
+fn age_color(age: f32) -> vec4<f32> {
+    if age < AGE_YOUNG {
+        return mix(palette.newborn, palette.young, age / AGE_YOUNG);
+    }
+    if age < AGE_MATURE {
+        return mix(palette.young, palette.mature, (age - AGE_YOUNG) / (AGE_MATURE - AGE_YOUNG));
+    }
+    return mix(palette.mature, palette.alive, min((age - AGE_MATURE) / (AGE_OLD - AGE_MATURE), 1.0));
+}
+
+fn cell_color(value: u32) -> vec4<f32> {
+    // Rules with more than two states store the state, each has its color
+    if palette.by_state != 0u {
+        return textureLoad(state_colors, vec2<i32>(i32(value), 0), 0);
+    }
+    let alive = (value & ALIVE_BIT) != 0u;
+    let level = value & LEVEL_MASK;
+    if !alive {
+        if palette.color_mode == AGE && level < palette.trail_length {
+            return mix(palette.trail, palette.dead, f32(level) / f32(palette.trail_length));
+        }
+        return palette.dead;
+    }
+    if palette.color_mode == AGE {
+        return age_color(f32(level));
+    }
+    if palette.color_mode == NEIGHBOURS {
+        return mix(palette.few_neighbours, palette.many_neighbours, f32(max(level, 1u) - 1u) / 7.0);
+    }
+    if palette.color_mode == DYING {
+        return select(palette.alive, palette.dying, level != 0u);
+    }
+    if palette.color_mode == BIRTH_SURVIVAL {
+        return select(palette.survived, palette.born, level != 0u);
+    }
+    return palette.alive;
+}
+
+// Signed distance to the edge of the cell's shape, negative inside. `p` goes from -0.5 to 0.5 across the cell
+fn shape_distance(p: vec2<f32>) -> f32 {
+    let half = 0.5 - palette.gap / 2.0;
+    if palette.shape == CIRCLE {
+        return length(p) - half;
+    }
+    let radius = select(0.0, half * 0.4, palette.shape == ROUNDED_SQUARE);
+    let q = abs(p) - vec2<f32>(half - radius);
+    return length(max(q, vec2<f32>(0.0))) + min(max(q.x, q.y), 0.0) - radius;
+}
+
+// Centre of a cell of the hexagonal lattice, odd rows are shifted half a cell right
+fn hexagon_centre(cell: vec2<i32>) -> vec2<f32> {
+    return vec2<f32>(cell) + vec2<f32>(0.5 + 0.5 * f32(cell.y & 1), 0.5);
+}
+
+// The cell a point is in on the hexagonal or triangular lattice, with the distance to its edge
+struct LatticeCell {
+    cell: vec2<i32>,
+    edge: f32,
+    // Centre and radius of the circle inside the cell
+    centre: vec2<f32>,
+    inradius: f32,
+};
+
+// The hexagon with the nearest centre, its edge is where another centre gets as near
+fn hexagon_at(position: vec2<f32>) -> LatticeCell {
+    // Candidates in the row and the rows above and below, with one more on each side for the edge
+    var candidates: array<vec2<i32>, 9>;
+    let row = i32(floor(position.y));
+    var nearest = 0;
+    var nearest_distance = 1e9;
+    for (var i = 0; i < 3; i++) {
+        let y = row + i - 1;
+        let x = i32(floor(position.x - 0.5 * f32(y & 1)));
+        for (var j = 0; j < 3; j++) {
+            let k = i * 3 + j;
+            candidates[k] = vec2<i32>(x + j - 1, y);
+            let d = distance(position, hexagon_centre(candidates[k]));
+            if d < nearest_distance {
+                nearest = k;
+                nearest_distance = d;
+            }
+        }
+    }
+    let centre = hexagon_centre(candidates[nearest]);
+    var edge = 1e9;
+    for (var k = 0; k < 9; k++) {
+        if k != nearest {
+            // Distance to the line halfway between the two centres
+            let other = hexagon_centre(candidates[k]);
+            let towards = other - centre;
+            edge = min(edge, dot(position - (centre + other) / 2.0, -towards) / length(towards));
+        }
+    }
+    return LatticeCell(candidates[nearest], edge, centre, 0.5);
+}
+
+// Distance from a point to the sides of a triangular cell, negative outside. See `triangle_depth` in lattice.rs
+fn triangle_distance(cell: vec2<i32>, position: vec2<f32>) -> f32 {
+    let from_apex = position.x - f32(cell.x) - 0.5;
+    var down = position.y - f32(cell.y);
+    if ((cell.x + cell.y) & 1) != 0 {
+        down = 1.0 - down;
+    }
+    // The slanted sides are at 45 degrees
+    return min((down - abs(from_apex)) * 0.70710678, 1.0 - down);
+}
+
+// The triangle of the column or of the ones on its sides the point is the deepest in
+fn triangle_at(position: vec2<f32>) -> LatticeCell {
+    let column = vec2<i32>(floor(position));
+    var cell = column;
+    var edge = -1e9;
+    for (var dx = -1; dx <= 1; dx++) {
+        let candidate = column + vec2<i32>(dx, 0);
+        let d = triangle_distance(candidate, position);
+        if d > edge {
+            cell = candidate;
+            edge = d;
+        }
+    }
+    var centre_y = f32(cell.y) + 1.0 - TRIANGLE_INRADIUS;
+    if ((cell.x + cell.y) & 1) != 0 {
+        centre_y = f32(cell.y) + TRIANGLE_INRADIUS;
+    }
+    return LatticeCell(cell, edge, vec2<f32>(f32(cell.x) + 0.5, centre_y), TRIANGLE_INRADIUS);
+}
+
+fn texel_color(texel: vec2<i32>) -> vec4<f32> {
+    let current = u32(round(textureLoad(cells, texel, 0).r * 255.0));
+    var color = cell_color(current);
+    if palette.progress < 1.0 {
+        // Births fade in and deaths fade out over the time step
+        let before = u32(round(textureLoad(previous, texel, 0).r * 255.0));
+        color = mix(cell_color(before), color, palette.progress);
+    }
+    return color;
+}
+
+@fragment
+fn fragment(mesh: MeshVertexOutput) -> @location(0) vec4<f32> {
+    // One texel per cell, read it exactly instead of sampling
+    let texels = vec2<i32>(textureDimensions(cells));
+    if palette.overview != 0u {
+        // Each texel is the density of living cells in a block of cells
+        let texel = min(vec2<i32>(mesh.uv * vec2<f32>(texels)), texels - 1);
+        return mix(palette.dead, palette.alive, textureLoad(cells, texel, 0).r);
+    }
+    // Position in the cells of the tile, inside the apron
+    let size = texels - 2 * APRON;
+    let position = mesh.uv * vec2<f32>(size);
+    if palette.lattice != SQUARE {
+        var at: LatticeCell;
+        if palette.lattice == HEXAGONAL {
+            at = hexagon_at(position);
+        } else {
+            at = triangle_at(position);
+        }
+        let color = texel_color(clamp(at.cell + APRON, vec2<i32>(0), texels - 1));
+        if palette.shape == TEXEL {
+            return color;
+        }
+        // The cells shrink by the gap, circles fit in them
+        var distance = palette.gap / 2.0 - at.edge;
+        if palette.shape == CIRCLE {
+            distance = length(position - at.centre) - (at.inradius - palette.gap / 2.0);
+        }
+        let edge = fwidth(distance);
+        return mix(color, palette.dead, smoothstep(-edge, edge, distance));
+    }
+    let cell = min(vec2<i32>(position), size - 1);
+    let color = texel_color(cell + APRON);
+    if palette.shape == TEXEL {
+        return color;
+    }
+    // Antialiased edge, the space around the shape is dead
+    let distance = shape_distance(fract(position) - 0.5);
+    let edge = fwidth(distance);
+    return mix(color, palette.dead, smoothstep(-edge, edge, distance));
+}
@@ -0,0 +1,130 @@
+use bevy::{
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+    render::render_resource::{AsBindGroup, ShaderRef},
+    sprite::Material2d,
+};
+
+use crate::game_of_life::GameSettings;
+use crate::rules::Rule;
+
+/**
+ *  Material the board is drawn with. The texture holds one byte per cell (see `palette::encode_cell`)
+ *  and the shader turns it into a color with the palette, so changing colors doesn't touch the texture.
+ *  The previous generation is kept in a second texture to blend between the two.
+ *  Rules with more than two states color each state from a table shared by every tile (see `palette::state_colors`).
+ *  Big enough cells can be drawn as shapes with a gap between them, small ones are always plain texels.
+ *  On the hexagonal and triangular lattices each pixel takes the cell it falls in, whatever the size of the cells.
+ */
+
+const MIN_SHAPE_PIXELS: f32 = 6.; // Below this size on screen cells are drawn as plain squares
+
+#[derive(PartialEq, Debug, Copy, Clone, Default)]
+pub enum CellShape {
+    #[default]
+    Square,
+    RoundedSquare,
+    Circle,
+}
+
+#[derive(AsBindGroup, TypeUuid, TypePath, Debug, Clone)]
+#[uuid = "6b0f6c0e-3c57-4f1e-9d0a-2f4b7f0b6a51"]
+pub struct BoardMaterial {
+    #[uniform(0)]
+    pub palette: BoardPalette,
+    #[texture(1)]
+    pub cells: Handle<Image>,
+    // The cells on the generation before, faded out while interpolating
+    #[texture(2)]
+    pub previous: Handle<Image>,
+    // One texel per state
+    #[texture(3)]
+    pub state_colors: Handle<Image>,
+}
+
+pub use uniform::BoardPalette;
+// The layout checks `ShaderType` generates next to the struct are reported as unused by newer compilers
+#[allow(dead_code)]
+mod uniform {
+    use bevy::{prelude::*, render::render_resource::ShaderType};
+
+    // The palette and the settings the shader needs to color a cell, colors go from 0 to 1
+    #[derive(ShaderType, Debug, Clone, Copy, PartialEq)]
+    pub struct BoardPalette {
+        pub(super) alive: Vec4,
+        pub(super) dead: Vec4,
+        pub(super) newborn: Vec4,
+        pub(super) young: Vec4,
+        pub(super) mature: Vec4,
+        pub(super) trail: Vec4,
+        pub(super) few_neighbours: Vec4,
+        pub(super) many_neighbours: Vec4,
+        pub(super) dying: Vec4,
+        pub(super) born: Vec4,
+        pub(super) survived: Vec4,
+        pub(super) color_mode: u32,
+        pub(super) trail_length: u32,
+        // How far into the time step we are, from 0 (previous generation) to 1 (current one)
+        pub(super) progress: f32,
+        // 0 draws one texel per cell, otherwise `CellShape` + 1
+        pub(super) shape: u32,
+        // Space between the shapes as a fraction of the cell
+        pub(super) gap: f32,
+        // The texture holds the density of living cells instead of cells
+        pub(super) overview: u32,
+        // The textures hold states colored from `state_colors`
+        pub(super) by_state: u32,
+        // Same order as `Lattice`
+        pub(super) lattice: u32,
+    }
+}
+
+impl Material2d for BoardMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/board.wgsl".into()
+    }
+}
+
+impl BoardPalette {
+    // `progress` through the time step and `cell_pixels`, the on screen size of a cell
+    pub fn new(settings: &GameSettings, rule: &Rule, progress: f32, cell_pixels: f32) -> Self {
+        let palette = &settings.palette;
+        let color = |c: [u8; 4]| Vec4::from_array(c.map(|channel| channel as f32 / 255.));
+        BoardPalette {
+            alive: color(palette.alive),
+            dead: color(palette.dead),
+            newborn: color(palette.newborn),
+            young: color(palette.young),
+            mature: color(palette.mature),
+            trail: color(palette.trail),
+            few_neighbours: color(palette.few_neighbours),
+            many_neighbours: color(palette.many_neighbours),
+            dying: color(palette.dying),
+            born: color(palette.born),
+            survived: color(palette.survived),
+            color_mode: settings.color_mode as u32,
+            trail_length: settings.trail_length as u32,
+            progress,
+            shape: if cell_pixels < MIN_SHAPE_PIXELS
+                || (settings.cell_shape == CellShape::Square && settings.cell_gap == 0.)
+            {
+                0
+            } else {
+                settings.cell_shape as u32 + 1
+            },
+            gap: settings.cell_gap,
+            overview: 0,
+            by_state: rule.colored_by_state() as u32,
+            lattice: rule.lattice() as u32,
+        }
+    }
+    // For the zoomed out overview of the board
+    pub fn overview(self) -> Self {
+        BoardPalette {
+            progress: 1.,
+            shape: 0,
+            overview: 1,
+            ..self
+        }
+    }
+}
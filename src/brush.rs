@@ -0,0 +1,589 @@
+use std::collections::{HashSet, VecDeque};
+
+use bevy::prelude::*;
+
+use crate::lattice::{self, Lattice};
+use crate::universe::{State, MAX_CELLS};
+
+/**
+ *  Brush settings and the geometry of what a stroke covers on the board
+ */
+
+////////////////////////////////////////////////////////////////////////
+/// RESOURCES
+////////////////////////////////////////////////////////////////////////
+
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub enum BrushMode {
+    #[default]
+    Paint,
+    Erase,
+    Invert,
+}
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub enum BrushTool {
+    #[default]
+    Freehand,
+    Line,
+    Rectangle,
+    Ellipse,
+    // Bucket fill of the region under the cursor
+    Fill,
+    // Randomly sets the cells under the brush
+    Spray,
+}
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub enum Connectivity {
+    // Only through the sides of a cell
+    #[default]
+    Four,
+    // Through the sides and the corners
+    Eight,
+}
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub enum Symmetry {
+    #[default]
+    None,
+    // Mirrored left to right
+    Horizontal,
+    // Mirrored top to bottom
+    Vertical,
+    // 4-fold rotational
+    Rotational,
+    // 4-fold rotational plus the mirror image of each rotation
+    Dihedral,
+}
+#[derive(Resource, Debug)]
+pub struct Brush {
+    pub size: u8,
+    pub mode: BrushMode,
+    // State painted cells are set to, alive unless the rule has more states
+    pub state: State,
+    // Only touches the cell under the cursor, ignoring `size`
+    pub precise: bool,
+    pub tool: BrushTool,
+    // Rectangles and ellipses are filled instead of outlined
+    pub filled: bool,
+    pub symmetry: Symmetry,
+    // Cell the strokes are mirrored around, the board centre if none
+    pub symmetry_center: Option<IVec2>,
+    pub connectivity: Connectivity,
+    // Chance of each cell under the spray being painted on a frame
+    pub spray_density: f32,
+}
+// The stroke currently being drawn, it lives from the moment a button is pressed until it's released
+#[derive(Resource, Debug, Default)]
+pub struct Stroke {
+    // Cell under the cursor on the previous frame, used to fill the gaps when the mouse moves fast
+    pub last: Option<IVec2>,
+    // Where the shape being rubber-banded started, as a cell and as a world position
+    pub anchor: Option<(IVec2, Vec2)>,
+    // The stroke was started with the right button
+    pub erasing: bool,
+    // Cells already inverted during this stroke so they don't flicker every frame
+    pub inverted: HashSet<IVec2>,
+}
+
+impl Default for Brush {
+    fn default() -> Self {
+        Brush {
+            size: 1,
+            mode: BrushMode::default(),
+            state: State::ALIVE,
+            precise: false,
+            tool: BrushTool::default(),
+            filled: false,
+            symmetry: Symmetry::default(),
+            symmetry_center: None,
+            connectivity: Connectivity::default(),
+            spray_density: 0.1,
+        }
+    }
+}
+impl Brush {
+    // Radius of the stamp in cells
+    pub fn radius(&self) -> i32 {
+        if self.precise {
+            0
+        } else {
+            self.size as i32
+        }
+    }
+}
+impl Symmetry {
+    // The maps that take a stroke (relative to the centre) to each of its copies, identity included. Hexagonal
+    // cells are sheared first (see `lattice::to_sheared`), there a sixth of a turn is a map of whole cells
+    fn transforms(&self, lattice: Lattice) -> Vec<Mat2> {
+        let (rotations, mirror_x, mirror_y) = if lattice == Lattice::Hexagonal {
+            let turn = Mat2::from_cols(Vec2::new(1., 1.), Vec2::new(-1., 0.));
+            let rotations: Vec<Mat2> = (0..6)
+                .scan(Mat2::IDENTITY, |rotation, _| {
+                    let current = *rotation;
+                    *rotation = turn * *rotation;
+                    Some(current)
+                })
+                .collect();
+            (
+                rotations,
+                Mat2::from_cols(Vec2::new(-1., 0.), Vec2::new(1., 1.)),
+                Mat2::from_cols(Vec2::new(1., 0.), Vec2::new(-1., -1.)),
+            )
+        } else {
+            (
+                vec![
+                    Mat2::IDENTITY,
+                    Mat2::from_cols(Vec2::new(0., 1.), Vec2::new(-1., 0.)),
+                    Mat2::from_cols(Vec2::new(-1., 0.), Vec2::new(0., -1.)),
+                    Mat2::from_cols(Vec2::new(0., -1.), Vec2::new(1., 0.)),
+                ],
+                Mat2::from_diagonal(Vec2::new(-1., 1.)),
+                Mat2::from_diagonal(Vec2::new(1., -1.)),
+            )
+        };
+        match self {
+            Symmetry::None => vec![Mat2::IDENTITY],
+            Symmetry::Horizontal => vec![Mat2::IDENTITY, mirror_x],
+            Symmetry::Vertical => vec![Mat2::IDENTITY, mirror_y],
+            Symmetry::Rotational => rotations,
+            Symmetry::Dihedral => rotations
+                .iter()
+                .flat_map(|rotation| [*rotation, *rotation * mirror_x])
+                .collect(),
+        }
+    }
+    // How many times a stroke is turned by the rotational symmetries
+    pub fn turns(lattice: Lattice) -> usize {
+        match lattice {
+            Lattice::Square => 4,
+            Lattice::Hexagonal => 6,
+            Lattice::Triangular => 3,
+        }
+    }
+}
+impl Stroke {
+    pub fn is_active(&self) -> bool {
+        self.last.is_some() || self.anchor.is_some()
+    }
+    pub fn clear(&mut self) {
+        *self = Stroke::default();
+    }
+}
+
+////////////////////////////////////////////////////////////////////////
+/// GEOMETRY
+////////////////////////////////////////////////////////////////////////
+
+// Whether a cell is under the brush centred on `center`: inside its circle on the square lattice, at most `radius`
+// steps away on the hexagonal one. Triangles are twice as wide as they're high, so the ones beside the centre
+// are a bit more than a cell away and get half a cell of slack
+pub fn in_stamp(cell: IVec2, center: IVec2, radius: i32, lattice: Lattice) -> bool {
+    match lattice {
+        Lattice::Square => {
+            let offset = cell - center;
+            ((offset.x.pow(2) + offset.y.pow(2)) as f32).sqrt() <= radius as f32
+        }
+        Lattice::Hexagonal => {
+            let offset = lattice::to_sheared(cell) - lattice::to_sheared(center);
+            (offset.x.abs() + offset.y.abs() + (offset.x - offset.y).abs()) / 2 <= radius
+        }
+        Lattice::Triangular => {
+            lattice.centre(cell).distance(lattice.centre(center)) <= radius as f32 + 0.5
+        }
+    }
+}
+// Cells under the brush centred on `center`
+pub fn stamp(center: IVec2, radius: i32, lattice: Lattice) -> impl Iterator<Item = IVec2> {
+    // A row more than the radius, triangles reach a bit further than their centres
+    let reach = radius + 1;
+    (-reach..=reach)
+        .flat_map(move |bx| (-reach..=reach).map(move |by| center + IVec2::new(bx, by)))
+        .filter(move |cell| in_stamp(*cell, center, radius, lattice))
+}
+
+// Cells on the segment between `from` and `to` (Bresenham), both ends included
+pub fn line(from: IVec2, to: IVec2) -> Vec<IVec2> {
+    let dx = (to.x - from.x).abs();
+    let dy = -(to.y - from.y).abs();
+    let sx = if from.x < to.x { 1 } else { -1 };
+    let sy = if from.y < to.y { 1 } else { -1 };
+    let mut err = dx + dy;
+    let mut current = from;
+    let mut cells = vec![current];
+    while current != to {
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            current.x += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            current.y += sy;
+        }
+        cells.push(current);
+    }
+    cells
+}
+
+// Cells of the axis aligned rectangle with `a` and `b` as opposite corners
+pub fn rectangle(a: IVec2, b: IVec2, filled: bool) -> Vec<IVec2> {
+    let min = a.min(b);
+    let max = a.max(b);
+    let mut cells = vec![];
+    for y in min.y..=max.y {
+        for x in min.x..=max.x {
+            if filled || x == min.x || x == max.x || y == min.y || y == max.y {
+                cells.push(IVec2::new(x, y));
+            }
+        }
+    }
+    cells
+}
+
+// Cells of the ellipse inscribed in the rectangle with `a` and `b` as opposite corners
+pub fn ellipse(a: IVec2, b: IVec2, filled: bool) -> Vec<IVec2> {
+    let min = a.min(b);
+    let max = a.max(b);
+    let center = (min + max).as_vec2() / 2.;
+    // Half a cell of slack so that thin ellipses still cover the cells they pass through
+    let radii = (max - min).as_vec2() / 2. + 0.5;
+    let inside = |x: i32, y: i32| {
+        let d = (Vec2::new(x as f32, y as f32) - center) / radii;
+        d.length_squared() <= 1.
+    };
+
+    let mut cells = vec![];
+    for y in min.y..=max.y {
+        for x in min.x..=max.x {
+            if !inside(x, y) {
+                continue;
+            }
+            // A cell is on the outline if one of its sides touches the outside
+            let on_edge =
+                !inside(x - 1, y) || !inside(x + 1, y) || !inside(x, y - 1) || !inside(x, y + 1);
+            if filled || on_edge {
+                cells.push(IVec2::new(x, y));
+            }
+        }
+    }
+    cells
+}
+
+// Cells covered by a shape drawn with the brush from `a` to `b`, outlines are as thick as the brush
+pub fn shape(brush: &Brush, a: IVec2, b: IVec2, lattice: Lattice) -> HashSet<IVec2> {
+    let radius = brush.radius();
+    let (outline, interior) = match brush.tool {
+        BrushTool::Freehand | BrushTool::Line | BrushTool::Spray => (line(a, b), vec![]),
+        BrushTool::Fill => (vec![], vec![]),
+        BrushTool::Rectangle => (
+            rectangle(a, b, false),
+            if brush.filled {
+                rectangle(a, b, true)
+            } else {
+                vec![]
+            },
+        ),
+        BrushTool::Ellipse => (
+            ellipse(a, b, false),
+            if brush.filled {
+                ellipse(a, b, true)
+            } else {
+                vec![]
+            },
+        ),
+    };
+    outline
+        .into_iter()
+        .flat_map(|cell| stamp(cell, radius, lattice))
+        .chain(interior)
+        .collect()
+}
+
+// Contiguous region around `start` (within a board of `size` cells) whose cells satisfy `same`. Regions of more
+// than `MAX_CELLS` cells are left alone, that's most of a big board
+pub fn flood_fill(
+    start: IVec2,
+    size: IVec2,
+    connectivity: Connectivity,
+    lattice: Lattice,
+    same: impl Fn(IVec2) -> bool,
+) -> Vec<IVec2> {
+    let in_board = |cell: IVec2| cell.cmpge(IVec2::ZERO).all() && cell.cmplt(size).all();
+    if !in_board(start) || !same(start) {
+        return vec![];
+    }
+    let directions: &[IVec2] = match connectivity {
+        Connectivity::Four => &[IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y],
+        Connectivity::Eight => &[
+            IVec2::X,
+            IVec2::NEG_X,
+            IVec2::Y,
+            IVec2::NEG_Y,
+            IVec2::ONE,
+            IVec2::NEG_ONE,
+            IVec2::new(1, -1),
+            IVec2::new(-1, 1),
+        ],
+    };
+    // Hexagons touch the 6 cells around them by a side, they have no neighbour by a corner only. Triangles
+    // touch 3 cells by a side and 9 more by a corner
+    let neighbours = |cell: IVec2| -> Vec<IVec2> {
+        match (lattice, connectivity) {
+            (Lattice::Hexagonal, _) => lattice::hexagonal_neighbours(cell).to_vec(),
+            (Lattice::Triangular, Connectivity::Four) => lattice.sides(cell),
+            (Lattice::Triangular, Connectivity::Eight) => {
+                lattice::triangular_neighbours(cell).to_vec()
+            }
+            (Lattice::Square, _) => directions
+                .iter()
+                .map(|direction| cell + *direction)
+                .collect(),
+        }
+    };
+
+    let mut visited = HashSet::from([start]);
+    let mut queue = VecDeque::from([start]);
+    while let Some(cell) = queue.pop_front() {
+        for next in neighbours(cell) {
+            if in_board(next) && same(next) && visited.insert(next) {
+                queue.push_back(next);
+            }
+        }
+        if visited.len() > MAX_CELLS {
+            return vec![];
+        }
+    }
+    visited.into_iter().collect()
+}
+
+// Adds the mirrored copies of `cells` around `center`
+pub fn symmetric(
+    cells: impl IntoIterator<Item = IVec2>,
+    symmetry: Symmetry,
+    center: IVec2,
+    lattice: Lattice,
+) -> HashSet<IVec2> {
+    cells
+        .into_iter()
+        .flat_map(|cell| copies(cell, symmetry, center, lattice))
+        .collect()
+}
+// The copies of a cell around `center`, the first is the cell itself. Each cell gets its copies in the same order
+pub fn copies(cell: IVec2, symmetry: Symmetry, center: IVec2, lattice: Lattice) -> Vec<IVec2> {
+    match lattice {
+        Lattice::Square => symmetry
+            .transforms(lattice)
+            .iter()
+            .map(|transform| center + (*transform * (cell - center).as_vec2()).round().as_ivec2())
+            .collect(),
+        Lattice::Hexagonal => {
+            let (cell, center) = (lattice::to_sheared(cell), lattice::to_sheared(center));
+            symmetry
+                .transforms(lattice)
+                .iter()
+                .map(|transform| {
+                    let offset = (*transform * (cell - center).as_vec2()).round().as_ivec2();
+                    lattice::from_sheared(center + offset)
+                })
+                .collect()
+        }
+        Lattice::Triangular => triangular_copies(cell, symmetry, center),
+    }
+}
+// Turns go around the middle of the centre triangle, a third of a turn at a time. The top to bottom mirror is
+// across its horizontal side, so the triangles pointing up and down swap like they do on screen
+fn triangular_copies(cell: IVec2, symmetry: Symmetry, center: IVec2) -> Vec<IVec2> {
+    // The middle of a triangle, with the rows stretched so the triangles are equilateral
+    let stretch = Vec2::new(1., 3f32.sqrt());
+    let middle = |cell: IVec2| {
+        let down = if lattice::points_up(cell) { 2. } else { 1. } / 3.;
+        Vec2::new(cell.x as f32 + 0.5, cell.y as f32 + down) * stretch
+    };
+    let turn = |cell: IVec2, turns: usize| {
+        let angle = turns as f32 * std::f32::consts::TAU / 3.;
+        let position =
+            middle(center) + Vec2::from_angle(angle).rotate(middle(cell) - middle(center));
+        let position = position / stretch;
+        IVec2::new((position.x - 0.5).round() as i32, position.y.floor() as i32)
+    };
+    let mirror_x = |cell: IVec2| IVec2::new(2 * center.x - cell.x, cell.y);
+    let base = if lattice::points_up(center) {
+        center.y + 1
+    } else {
+        center.y
+    };
+    let mirror_y = |cell: IVec2| IVec2::new(cell.x, 2 * base - 1 - cell.y);
+    match symmetry {
+        Symmetry::None => vec![cell],
+        Symmetry::Horizontal => vec![cell, mirror_x(cell)],
+        Symmetry::Vertical => vec![cell, mirror_y(cell)],
+        Symmetry::Rotational => (0..3).map(|turns| turn(cell, turns)).collect(),
+        Symmetry::Dihedral => (0..3)
+            .flat_map(|turns| [turn(cell, turns), turn(mirror_x(cell), turns)])
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hexagonal_stamp_is_the_cells_steps_away() {
+        for center in [IVec2::new(4, 4), IVec2::new(4, 5)] {
+            let mut ring: Vec<IVec2> = stamp(center, 1, Lattice::Hexagonal).collect();
+            let mut expected = lattice::hexagonal_neighbours(center).to_vec();
+            expected.push(center);
+            ring.sort_by_key(|cell| (cell.x, cell.y));
+            expected.sort_by_key(|cell| (cell.x, cell.y));
+            assert_eq!(ring, expected);
+            assert_eq!(stamp(center, 3, Lattice::Hexagonal).count(), 37);
+        }
+    }
+
+    #[test]
+    fn hexagonal_copies_stay_on_the_ring() {
+        for center in [IVec2::new(4, 4), IVec2::new(4, 5)] {
+            let neighbours = lattice::hexagonal_neighbours(center);
+            let turned = copies(
+                neighbours[0],
+                Symmetry::Rotational,
+                center,
+                Lattice::Hexagonal,
+            );
+            assert_eq!(turned, neighbours.to_vec());
+            // Mirrored left to right the top right neighbour goes to the top left
+            let mirrored = copies(
+                neighbours[0],
+                Symmetry::Horizontal,
+                center,
+                Lattice::Hexagonal,
+            );
+            assert_eq!(mirrored, vec![neighbours[0], neighbours[5]]);
+            let mirrored = copies(
+                neighbours[0],
+                Symmetry::Vertical,
+                center,
+                Lattice::Hexagonal,
+            );
+            assert_eq!(mirrored, vec![neighbours[0], neighbours[2]]);
+            // Off the mirror axes, so every copy is a different cell
+            let cell =
+                lattice::hexagonal_neighbours(lattice::hexagonal_neighbours(neighbours[0])[0])[1];
+            let all: HashSet<IVec2> = copies(cell, Symmetry::Dihedral, center, Lattice::Hexagonal)
+                .into_iter()
+                .collect();
+            assert_eq!(all.len(), 12);
+            assert!(all
+                .iter()
+                .all(|c| in_stamp(*c, center, 3, Lattice::Hexagonal)));
+            assert!(all
+                .iter()
+                .all(|c| !in_stamp(*c, center, 2, Lattice::Hexagonal)));
+        }
+    }
+
+    #[test]
+    fn triangular_copies_keep_the_orientation() {
+        for center in [IVec2::new(4, 4), IVec2::new(5, 4)] {
+            let sides = Lattice::Triangular.sides(center);
+            let turned: HashSet<IVec2> =
+                copies(sides[0], Symmetry::Rotational, center, Lattice::Triangular)
+                    .into_iter()
+                    .collect();
+            assert_eq!(turned, sides.iter().copied().collect());
+            assert_eq!(
+                copies(center, Symmetry::Rotational, center, Lattice::Triangular),
+                vec![center; 3]
+            );
+            // The top to bottom mirror is across the horizontal side
+            assert_eq!(
+                copies(center, Symmetry::Vertical, center, Lattice::Triangular),
+                vec![center, sides[1]]
+            );
+            for x in 0..10 {
+                for y in 0..10 {
+                    let cell = IVec2::new(x, y);
+                    let [_, across] =
+                        copies(cell, Symmetry::Horizontal, center, Lattice::Triangular)[..]
+                    else {
+                        panic!()
+                    };
+                    assert_eq!(lattice::points_up(across), lattice::points_up(cell));
+                    let [_, across] =
+                        copies(cell, Symmetry::Vertical, center, Lattice::Triangular)[..]
+                    else {
+                        panic!()
+                    };
+                    assert_ne!(lattice::points_up(across), lattice::points_up(cell));
+                    // Turning three times gets back to the cell
+                    let turned = copies(cell, Symmetry::Rotational, center, Lattice::Triangular);
+                    let back = copies(turned[1], Symmetry::Rotational, center, Lattice::Triangular);
+                    assert_eq!(back[2], cell);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn triangular_stamp_reaches_the_sides() {
+        for center in [IVec2::new(4, 4), IVec2::new(5, 4)] {
+            assert_eq!(
+                stamp(center, 0, Lattice::Triangular).collect::<Vec<_>>(),
+                vec![center]
+            );
+            let cells: HashSet<IVec2> = stamp(center, 1, Lattice::Triangular).collect();
+            assert!(Lattice::Triangular
+                .sides(center)
+                .iter()
+                .all(|side| cells.contains(side)));
+        }
+    }
+
+    #[test]
+    fn hexagonal_fill_stays_inside_a_ring() {
+        for center in [IVec2::new(4, 4), IVec2::new(4, 5)] {
+            let walls = lattice::hexagonal_neighbours(center);
+            for connectivity in [Connectivity::Four, Connectivity::Eight] {
+                let region = flood_fill(
+                    center,
+                    IVec2::splat(10),
+                    connectivity,
+                    Lattice::Hexagonal,
+                    |cell| !walls.contains(&cell),
+                );
+                assert_eq!(region, vec![center]);
+            }
+            // Every neighbour is reached from the centre through a side
+            let region = flood_fill(
+                center,
+                IVec2::splat(10),
+                Connectivity::Four,
+                Lattice::Hexagonal,
+                |cell| cell == center || walls.contains(&cell),
+            );
+            assert_eq!(region.len(), 7);
+        }
+    }
+
+    #[test]
+    fn triangular_fill_goes_through_sides_or_corners() {
+        for center in [IVec2::new(4, 4), IVec2::new(5, 4)] {
+            let fill = |connectivity, walls: &[IVec2]| {
+                flood_fill(
+                    center,
+                    IVec2::splat(10),
+                    connectivity,
+                    Lattice::Triangular,
+                    |cell| !walls.contains(&cell),
+                )
+            };
+            let sides = Lattice::Triangular.sides(center);
+            let around = lattice::triangular_neighbours(center);
+            assert_eq!(fill(Connectivity::Four, &sides), vec![center]);
+            assert_eq!(fill(Connectivity::Eight, &around), vec![center]);
+            // Walled by its sides, the corners still lead out
+            assert_eq!(fill(Connectivity::Eight, &sides).len(), 100 - 3);
+            assert!(sides.iter().all(|side| around.contains(side)));
+        }
+    }
+}
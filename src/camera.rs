@@ -0,0 +1,117 @@
+use bevy::{input::mouse::MouseWheel, prelude::*, window::PrimaryWindow};
+
+/**
+ *  This plugin lets the board be panned (middle drag or WASD) and zoomed (scroll wheel)
+ */
+
+#[derive(Default)]
+pub struct CameraControls;
+
+#[derive(Component)]
+pub struct MainCamera;
+
+const PAN_SPEED: f32 = 600.; // Pixels per second when panning with the keyboard
+const ZOOM_STEP: f32 = 1.1;
+const MIN_ZOOM: f32 = 0.02;
+const MAX_ZOOM: f32 = 50.;
+
+impl Plugin for CameraControls {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (pan_camera, zoom_camera));
+    }
+}
+
+////////////////////////////////////////////////////////////////////////
+/// SYSTEMS
+////////////////////////////////////////////////////////////////////////
+
+fn pan_camera(
+    mut q_camera: Query<(&mut Transform, &OrthographicProjection), With<MainCamera>>,
+    q_win: Query<&Window, With<PrimaryWindow>>,
+    keys: Res<Input<KeyCode>>,
+    buttons: Res<Input<MouseButton>>,
+    time: Res<Time>,
+    mut last_cursor: Local<Option<Vec2>>,
+    mut eguic: bevy_egui::EguiContexts,
+) {
+    let (mut transform, projection) = q_camera.single_mut();
+    let win = q_win.single();
+
+    // Keyboard panning, unless we're typing in the UI
+    if !eguic.ctx_mut().wants_keyboard_input() {
+        let mut direction = Vec2::ZERO;
+        if keys.pressed(KeyCode::W) {
+            direction.y += 1.;
+        }
+        if keys.pressed(KeyCode::S) {
+            direction.y -= 1.;
+        }
+        if keys.pressed(KeyCode::A) {
+            direction.x -= 1.;
+        }
+        if keys.pressed(KeyCode::D) {
+            direction.x += 1.;
+        }
+        let delta = direction * PAN_SPEED * projection.scale * time.delta_seconds();
+        transform.translation += delta.extend(0.);
+    }
+
+    // Middle mouse drag, the point grabbed stays under the cursor
+    let cursor = win.cursor_position();
+    if buttons.pressed(MouseButton::Middle) {
+        if let (Some(previous), Some(current)) = (*last_cursor, cursor) {
+            let delta = (current - previous) * Vec2::new(-1., 1.) * projection.scale;
+            transform.translation += delta.extend(0.);
+        }
+        *last_cursor = cursor;
+    } else {
+        *last_cursor = None;
+    }
+}
+
+fn zoom_camera(
+    mut q_camera: Query<
+        (
+            &Camera,
+            &mut Transform,
+            &GlobalTransform,
+            &mut OrthographicProjection,
+        ),
+        With<MainCamera>,
+    >,
+    q_win: Query<&Window, With<PrimaryWindow>>,
+    mut scroll_events: EventReader<MouseWheel>,
+    mut eguic: bevy_egui::EguiContexts,
+) {
+    let over_ui = eguic.ctx_mut().is_pointer_over_area();
+    let scroll: f32 = scroll_events.iter().map(|ev| ev.y.signum()).sum();
+    if scroll == 0. || over_ui {
+        return;
+    }
+    let (camera, mut transform, global_transform, mut projection) = q_camera.single_mut();
+    let win = q_win.single();
+
+    let old_scale = projection.scale;
+    projection.scale = (old_scale * ZOOM_STEP.powf(-scroll)).clamp(MIN_ZOOM, MAX_ZOOM);
+
+    // Keep the point under the cursor where it is
+    if let Some(cursor) = cursor_to_world(win, camera, global_transform) {
+        let camera_position = transform.translation.truncate();
+        let new_position = cursor - (cursor - camera_position) * (projection.scale / old_scale);
+        transform.translation = new_position.extend(transform.translation.z);
+    }
+}
+
+////////////////////////////////////////////////////////////////////////
+/// UTILS
+////////////////////////////////////////////////////////////////////////
+
+// The world position the cursor is pointing at
+pub fn cursor_to_world(
+    win: &Window,
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+) -> Option<Vec2> {
+    win.cursor_position()
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor))
+}
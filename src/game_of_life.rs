@@ -1,71 +1,140 @@
 use bevy::diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin};
-use bevy::input::mouse::MouseButtonInput;
-use bevy::render::render_resource::TextureFormat;
-use bevy::window::{PrimaryWindow, WindowResized};
+use bevy::window::PrimaryWindow;
 use bevy::{
     core_pipeline::clear_color::ClearColorConfig,
     prelude::*,
+    sprite::Material2dPlugin,
     window::{PresentMode, WindowResolution},
 };
 use rand::Rng;
-
+use std::collections::HashSet;
+
+use crate::board_material::{BoardMaterial, CellShape};
+use crate::brush::{self, Brush, BrushMode, BrushTool, Stroke};
+use crate::camera::{self, MainCamera};
+use crate::grid::{self, GridSettings};
+use crate::lattice::{self, Lattice};
+use crate::palette::{ColorMode, Palette, Theme, Themes};
+use crate::rle::Pattern;
+use crate::rules::Rule;
+use crate::tiles;
+use crate::turmites::{self, Turmite, TurmiteSettings};
 use crate::ui::UIEvent;
-
-////////////////////////////////////////////////////////////////////////
-/// COMPONENTS
-////////////////////////////////////////////////////////////////////////
-#[derive(Component, Debug, Copy, Clone)]
-enum State {
-    ALIVE,
-    DEAD,
-}
-#[derive(Component)]
-struct Board;
+use crate::universe::{State, Universe, MAX_CELLS};
 
 ////////////////////////////////////////////////////////////////////////
 /// RESOURCES
 ////////////////////////////////////////////////////////////////////////
 
-#[derive(PartialEq, Debug, Resource, Copy, Clone)]
+#[derive(PartialEq, Debug, Resource, Copy, Clone, Default)]
 pub enum Seed {
+    #[default]
     Random,
     Spaceship,
     GosperGliderGun,
     SimkinGliderGun,
+    // One living cell, in the middle of the first row for one dimensional rules
+    SingleCell,
+    // An empty board that waits for the cells to be painted, see `UIEvent::Start`
+    Painted,
 }
 #[derive(Resource, Debug, Clone, Copy)]
 pub struct GameSettings {
+    // Size of the board in cells
+    pub board_width: u32,
+    pub board_height: u32,
+    // Size of a cell on screen at the default zoom, it doesn't change the board
     pub cell_size: u8,
     pub time_step_secs: f32,
-    pub alive_color: [u8; 4],
-    pub dead_color: [u8; 4],
+    // Reversible rules step back to the first generation instead of forward
+    pub backwards: bool,
+    pub palette: Palette,
     pub seed: Seed,
+    pub color_mode: ColorMode,
+    // Generations the trail of a dead cell lasts when colouring by age
+    pub trail_length: u16,
+    // Fade births in and deaths out over the time step instead of snapping between generations
+    pub interpolate: bool,
+    // Shape of the cells when they're big enough on screen and the space between them (fraction of a cell)
+    pub cell_shape: CellShape,
+    pub cell_gap: f32,
 }
-#[derive(Resource, Debug)]
-pub struct Brush {
-    pub size: u8,
+// Time in seconds the last generation was stepped at
+#[derive(Resource, Debug, Default)]
+pub struct LastStep(pub f64);
+// The cell under the cursor, if it's on the board
+#[derive(Resource, Debug, Default)]
+pub struct HoveredCell(pub Option<IVec2>);
+// Sent on every time step, whether the rule stepped or not
+#[derive(Event)]
+pub struct Tick;
+#[derive(Debug)]
+pub struct CellInfo {
+    pub state: State,
+    pub neighbours: u32,
+    pub next: State,
+    // Generations in its current state, it stops counting at u16::MAX
+    pub age: u16,
 }
-#[derive(Resource)]
-struct BoardHandle(Handle<Image>);
 #[derive(Resource, Debug)]
-struct BoardSize {
-    rows: u32,
-    columns: u32,
+pub struct BoardSize {
+    pub rows: u32,
+    pub columns: u32,
+    // Where the cells are drawn, it follows the rule
+    pub lattice: Lattice,
 }
 
-impl Default for Seed {
-    fn default() -> Self {
-        Seed::Random
+impl BoardSize {
+    pub fn contains(&self, cell: IVec2) -> bool {
+        cell.x >= 0 && cell.y >= 0 && cell.x < self.rows as i32 && cell.y < self.columns as i32
+    }
+    pub fn center(&self) -> IVec2 {
+        IVec2::new(self.rows as i32 / 2, self.columns as i32 / 2)
+    }
+    // Size of the board sprite in world units, it's centered on the origin
+    pub fn world_size(&self, cell_size: u8) -> Vec2 {
+        Vec2::new(self.rows as f32, self.columns as f32) * cell_size as f32
+    }
+    // The cell at a world position, the first row of the texture is at the top of the sprite
+    pub fn world_to_cell(&self, position: Vec2, cell_size: u8) -> IVec2 {
+        let half = self.world_size(cell_size) / 2.;
+        let cell = Vec2::new(position.x + half.x, half.y - position.y) / cell_size as f32;
+        self.lattice.cell_at(cell)
+    }
+    // The world position of the centre of a cell
+    pub fn cell_to_world(&self, cell: IVec2, cell_size: u8) -> Vec2 {
+        self.board_to_world(self.lattice.centre(cell), cell_size)
+    }
+    // The world positions of the corners of a cell
+    pub fn cell_outline(&self, cell: IVec2, cell_size: u8) -> Vec<Vec2> {
+        self.lattice
+            .outline(cell)
+            .into_iter()
+            .map(|corner| self.board_to_world(corner, cell_size))
+            .collect()
+    }
+    // A position in cells from the top left corner of the board
+    fn board_to_world(&self, position: Vec2, cell_size: u8) -> Vec2 {
+        let half = self.world_size(cell_size) / 2.;
+        let position = position * cell_size as f32;
+        Vec2::new(position.x - half.x, half.y - position.y)
     }
 }
 impl Default for GameSettings {
     fn default() -> Self {
         GameSettings {
+            board_width: 400,
+            board_height: 240,
             cell_size: 3,
             time_step_secs: 0.03,
-            alive_color: [64, 64, 243, 255],
-            dead_color: [0, 0, 0, 255],
+            backwards: false,
+            palette: Palette::default(),
             seed: Seed::default(),
+            color_mode: ColorMode::default(),
+            trail_length: 10,
+            interpolate: false,
+            cell_shape: CellShape::default(),
+            cell_gap: 0.,
         }
     }
 }
@@ -91,14 +160,26 @@ pub fn init() {
                     ..default()
                 })
                 .set(ImagePlugin::default_nearest()),
-            FrameTimeDiagnosticsPlugin::default(),
+            FrameTimeDiagnosticsPlugin,
             LogDiagnosticsPlugin::default(),
             crate::ui::GameOfLifeUI::default(),
+            crate::camera::CameraControls,
+            crate::turmites::Turmites,
+            Material2dPlugin::<BoardMaterial>::default(),
         ))
         .insert_resource(GameSettings::default())
-        .add_systems(Startup, setup)
+        .insert_resource(Rule::default())
+        .add_event::<Tick>()
+        .add_systems(Startup, (setup, tiles::setup))
         .add_systems(Update, process_cells)
-        .add_systems(Last, (handle_ui_events, handle_events))
+        .add_systems(
+            Last,
+            (
+                (handle_ui_events, handle_events),
+                (tiles::update_tiles, tiles::update_overview),
+            )
+                .chain(),
+        )
         .run();
 }
 
@@ -125,265 +206,387 @@ pub fn init() {
 ///////////////////////////////////////////////////////////////////////
 
 // Creates the entities and resources
-fn setup(
-    mut commands: Commands,
-    q_win: Query<&Window>,
-    mut images: ResMut<Assets<Image>>,
-    settings: Res<GameSettings>,
-) {
-    let win = q_win.single();
-    let board_settings = create_board(&settings, &win);
-    let mut board = board_settings.0;
-    let rows = board_settings.1;
-    let columns = board_settings.2;
+fn setup(mut commands: Commands, settings: Res<GameSettings>, rule: Res<Rule>) {
+    let rows = settings.board_width;
+    let columns = settings.board_height;
+    let board_size = BoardSize {
+        rows,
+        columns,
+        lattice: rule.lattice(),
+    };
 
-    // Seed the board
-    seed(&mut board, &settings);
-    // text setup
-    let image = images.add(board);
+    // Seed the board, the tiles are drawn from it on the first frame
+    let mut universe = Universe::new(rows, columns);
+    seed(&mut universe, &settings, &rule);
 
     // Initialize resources
-    commands.insert_resource(BoardHandle(image.clone()));
-    commands.insert_resource(BoardSize { rows, columns });
-    commands.insert_resource(Brush { size: 1 });
-
-    commands.spawn(Camera2dBundle {
-        camera_2d: Camera2d {
-            clear_color: ClearColorConfig::Custom(Color::BLACK),
-        },
-        ..default()
-    });
+    commands.insert_resource(universe);
+    commands.insert_resource(LastStep::default());
+    commands.insert_resource(Brush::default());
+    commands.insert_resource(Stroke::default());
+    commands.insert_resource(HoveredCell::default());
+    commands.insert_resource(Themes::load());
 
     commands
-        .spawn(SpriteBundle {
-            sprite: Sprite {
-                custom_size: Some(Vec2::new(win.width() as f32, win.height() as f32)),
-
-                ..default()
+        .spawn(Camera2dBundle {
+            camera_2d: Camera2d {
+                clear_color: ClearColorConfig::Custom(Color::BLACK),
             },
-            texture: image.clone(),
             ..default()
         })
-        .insert(Board);
-}
-fn create_board(settings: &GameSettings, win: &Window) -> (Image, u32, u32) {
-    let rows = (win.width() / settings.cell_size as f32).floor() as u32;
-    let columns = (win.height() / settings.cell_size as f32).floor() as u32;
-    let board = Image::new_fill(
-        bevy::render::render_resource::Extent3d {
-            width: rows,
-            height: columns,
-            depth_or_array_layers: 1,
-        },
-        bevy::render::render_resource::TextureDimension::D2,
-        &(settings.dead_color.clone()),
-        TextureFormat::Rgba8Unorm,
-    );
-    (board, rows, columns)
+        .insert(MainCamera);
+
+    commands.insert_resource(board_size);
 }
 
-// Updates the next_state of the cells and after all the cells have been updated, state=next_state
+// Advances the universe one generation every time step
+#[allow(clippy::too_many_arguments)]
 fn process_cells(
-    mut images: ResMut<Assets<Image>>,
-    board_handle: Res<BoardHandle>,
-    board_size: Res<BoardSize>,
-    mut next_state: Local<Vec<u8>>,
+    mut universe: ResMut<Universe>,
+    rule: Res<Rule>,
     settings: Res<GameSettings>,
-    mut previous_tick: Local<f64>,
+    mut last_step: ResMut<LastStep>,
     time: Res<Time>,
+    q_turmites: Query<(), With<Turmite>>,
+    turmite_settings: Res<TurmiteSettings>,
+    mut ticks: EventWriter<Tick>,
 ) {
     // Check in the system since run conditions mess up with the scheduling
     let time_step = settings.time_step_secs;
-    if time.elapsed_seconds_f64() - (*previous_tick) <= time_step as f64 {
-        return ();
+    if time.elapsed_seconds_f64() - last_step.0 <= time_step as f64 {
+        return;
     }
-    *previous_tick = time.elapsed_seconds_f64();
-    let h = &board_handle.0;
-
-    let board = images.get_mut(h).unwrap();
-    if next_state.len() != board.data.len() {
-        // Initialize the buffer containing the next state
-        *next_state = board.data.clone();
+    last_step.0 = time.elapsed_seconds_f64();
+    // A painted board waits to be started
+    if settings.seed == Seed::Painted && universe.generation() == 0 {
+        return;
     }
-    for i in 0..(board.data.len() / 4) {
-        // component
-        let c = i * 4;
-
-        let y = (i as f32 / board_size.rows as f32).floor() as i32;
-        let x = i as i32 - y * board_size.rows as i32;
-
-        let new_cell_state = cell_state(&board, x, y, &settings);
-
-        match new_cell_state {
-            State::ALIVE => {
-                next_state[c + 0] = settings.alive_color[0];
-                next_state[c + 1] = settings.alive_color[1];
-                next_state[c + 2] = settings.alive_color[2];
-                next_state[c + 3] = settings.alive_color[3];
-            }
-            State::DEAD => {
-                next_state[c + 0] = settings.dead_color[0];
-                next_state[c + 1] = settings.dead_color[1];
-                next_state[c + 2] = settings.dead_color[2];
-                next_state[c + 3] = settings.dead_color[3];
-            }
+    // Turmites walk on a still board unless the rule is left running
+    if !turmite_settings.freeze_board || q_turmites.is_empty() {
+        match settings.backwards && rule.is_reversible() {
+            true => universe.step_back(&rule),
+            false => universe.step(&rule),
         }
     }
-    board.data = next_state.clone();
+    ticks.send(Tick);
 }
 
 // // Events triggered by the ui
+#[allow(clippy::too_many_arguments)]
 fn handle_ui_events(
     mut ui_events: EventReader<UIEvent>,
-    mut images: ResMut<Assets<Image>>,
-    mut board_handle: ResMut<BoardHandle>,
     mut settings: ResMut<GameSettings>,
+    mut universe: ResMut<Universe>,
+    mut rule: ResMut<Rule>,
 
-    mut texture: Query<&mut Handle<Image>, With<Board>>, // The handle to the board's texture
-    mut commands: Commands,
-    q_win: Query<&Window>,
+    mut q_camera: Query<&mut Transform, With<MainCamera>>,
     mut board_size: ResMut<BoardSize>,
+    mut brush: ResMut<Brush>,
+    mut grid: ResMut<GridSettings>,
+    mut themes: ResMut<Themes>,
+    mut commands: Commands,
+    mut turmite_settings: ResMut<TurmiteSettings>,
+    q_turmites: Query<Entity, With<Turmite>>,
 ) {
     for ev in ui_events.iter() {
         match *ev {
-            UIEvent::ChangeColor(alive_color, dead_color) => {
-                info!("CHANGE COLOR {:?} {:?}", alive_color, dead_color);
-                let h = &board_handle.0;
-                let board = images.get_mut(h).unwrap();
-                // let mut data = board.data;
-                for i in 0..(board.data.len() / 4) {
-                    let c = i * 4;
-                    let state = State::cell_state(
-                        &[
-                            &board.data[c + 0],
-                            &board.data[c + 1],
-                            &board.data[c + 2],
-                            &board.data[c + 3],
-                        ],
-                        &settings,
-                    );
-                    match state {
-                        State::ALIVE => {
-                            board.data[c + 0] = alive_color[0];
-                            board.data[c + 1] = alive_color[1];
-                            board.data[c + 2] = alive_color[2];
-                            board.data[c + 3] = alive_color[3];
-                        }
-                        State::DEAD => {
-                            board.data[c + 0] = dead_color[0];
-                            board.data[c + 1] = dead_color[1];
-                            board.data[c + 2] = dead_color[2];
-                            board.data[c + 3] = dead_color[3];
-                        }
-                    }
-                }
-                settings.alive_color = alive_color;
-                settings.dead_color = dead_color;
+            UIEvent::ChangePalette(palette) => {
+                // The board is redrawn from the universe with the new colors
+                settings.palette = palette;
+            }
+            UIEvent::SaveTheme(ref name) => {
+                themes.insert(Theme {
+                    name: name.clone(),
+                    palette: settings.palette,
+                });
+                themes.save();
+            }
+            UIEvent::LoadThemes => {
+                *themes = Themes::load();
             }
             UIEvent::ChangeSeed(seed_value) => {
-                let h = &board_handle.0;
-                let board = images.get_mut(h).unwrap();
                 settings.seed = seed_value;
-                reset_board(board, &settings);
-                seed(board, &settings);
+                universe.clear();
+                seed(&mut universe, &settings, &rule);
+            }
+            UIEvent::Start => {
+                universe.step(&rule);
+            }
+            UIEvent::ChangeRule(ref new_rule) => {
+                universe.retain_states(new_rule.states());
+                // A space-time diagram starts over from its first row
+                let restart = new_rule.is_one_dimensional() || rule.is_one_dimensional();
+                *rule = new_rule.clone();
+                if !rule.is_reversible() {
+                    settings.backwards = false;
+                }
+                if restart {
+                    universe.clear();
+                    seed(&mut universe, &settings, &rule);
+                }
+                if brush.state.0 as u16 >= rule.states() {
+                    brush.state = State::ALIVE;
+                }
+                // The tiles are rebuilt for the new lattice
+                if board_size.lattice != rule.lattice() {
+                    board_size.lattice = rule.lattice();
+                }
+            }
+            UIEvent::PlacePattern(ref pattern) => {
+                let center = board_size.center();
+                place_pattern(&mut universe, pattern, center, board_size.lattice);
+            }
+            UIEvent::ChangeColorMode(color_mode) => {
+                settings.color_mode = color_mode;
+            }
+            UIEvent::ChangeTrailLength(trail_length) => {
+                settings.trail_length = trail_length;
+            }
+            UIEvent::ChangeInterpolate(interpolate) => {
+                settings.interpolate = interpolate;
+            }
+            UIEvent::ChangeCellShape(cell_shape, cell_gap) => {
+                settings.cell_shape = cell_shape;
+                settings.cell_gap = cell_gap;
             }
             UIEvent::ChangeTimestep(time_step) => {
                 settings.time_step_secs = time_step;
             }
+            UIEvent::ChangeBackwards(backwards) => {
+                settings.backwards = backwards;
+            }
+            UIEvent::ChangeBrushMode(mode) => {
+                brush.mode = mode;
+            }
+            UIEvent::ChangeBrushState(state) => {
+                brush.state = state;
+            }
+            UIEvent::ChangeTurmites(ref new_settings) => {
+                *turmite_settings = new_settings.clone();
+            }
+            UIEvent::RemoveTurmites => {
+                for entity in q_turmites.iter() {
+                    commands.entity(entity).despawn();
+                }
+            }
+            UIEvent::ChangeBrushPrecise(precise) => {
+                brush.precise = precise;
+            }
+            UIEvent::ChangeBrushTool(tool) => {
+                brush.tool = tool;
+            }
+            UIEvent::ChangeBrushFilled(filled) => {
+                brush.filled = filled;
+            }
+            UIEvent::ChangeSymmetry(symmetry) => {
+                brush.symmetry = symmetry;
+            }
+            UIEvent::ChangeSymmetryCenter(center) => {
+                brush.symmetry_center = center;
+            }
+            UIEvent::ChangeConnectivity(connectivity) => {
+                brush.connectivity = connectivity;
+            }
+            UIEvent::ChangeSprayDensity(density) => {
+                brush.spray_density = density;
+            }
             UIEvent::ChangeCellSize(cell_size) => {
-                images.remove(&board_handle.0);
+                // The cell size only scales the tiles, keep the camera on the same cells
+                let ratio = cell_size as f32 / settings.cell_size as f32;
+                q_camera.single_mut().translation *= Vec3::new(ratio, ratio, 1.);
                 settings.cell_size = cell_size;
-                let window = q_win.single();
-                let mut new_board = create_board(&settings, &window);
-                seed(&mut new_board.0, &settings);
-                let image_handle = images.add(new_board.0);
-                let mut texture = texture.single_mut();
-
-                *texture = image_handle.clone();
-                *board_handle = BoardHandle(image_handle.clone());
+            }
+            UIEvent::ResizeCanvas(width, height, anchor) => {
+                *universe = universe.resized(width, height, anchor);
+                settings.board_width = width;
+                settings.board_height = height;
+                // The tiles are rebuilt for the new size
                 *board_size = BoardSize {
-                    rows: new_board.1,
-                    columns: new_board.2,
+                    rows: width,
+                    columns: height,
+                    lattice: board_size.lattice,
                 };
             }
-
-            _ => {}
+            UIEvent::ChangeGrid(new_grid) => {
+                *grid = new_grid;
+            }
+            UIEvent::GoTo(coordinate) => {
+                let cell = grid::coordinate_to_cell(coordinate, &board_size);
+                let position = board_size.cell_to_world(cell, settings.cell_size);
+                let mut camera = q_camera.single_mut();
+                camera.translation.x = position.x;
+                camera.translation.y = position.y;
+            }
         }
     }
 }
+#[allow(clippy::too_many_arguments)]
 fn handle_events(
-    mut resize_events: EventReader<WindowResized>,
-    mut board_sprite: Query<&mut Sprite, With<Board>>,
     q_win: Query<&Window, With<PrimaryWindow>>,
+    q_camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
     keys: Res<Input<KeyCode>>,
     buttons: Res<Input<MouseButton>>,
     board_size: Res<BoardSize>,
-    mut images: ResMut<Assets<Image>>,
+    mut universe: ResMut<Universe>,
     mut brush: ResMut<Brush>,
-    board_handle: Res<BoardHandle>,
     mut exit: EventWriter<bevy::app::AppExit>,
     settings: Res<GameSettings>,
     mut eguic: bevy_egui::EguiContexts,
+    q_interactions: Query<&Interaction>,
+    mut stroke: ResMut<Stroke>,
+    mut hovered: ResMut<HoveredCell>,
+    mut commands: Commands,
+    mut turmite_settings: ResMut<TurmiteSettings>,
 ) {
-    // Resize the board sprite if the window's size has changed
-    for resize in resize_events.iter() {
-        let mut board = board_sprite.single_mut();
-        board.custom_size = Some(Vec2::new(resize.width, resize.height));
-    }
     // J: makes the brush size smaller
-    if keys.pressed(KeyCode::J) {
-        if brush.size > 1 {
-            brush.size -= 1;
-        }
+    if keys.pressed(KeyCode::J) && brush.size > 1 {
+        brush.size -= 1;
     }
-    // J: makes the brush size smaller
+    // K: makes the brush size bigger
     if keys.pressed(KeyCode::K) {
-        if brush.size < u8::MAX {
-            brush.size += 1;
-        }
+        brush.size = brush.size.saturating_add(1);
     }
     // Exit the app if we press Esc
     if keys.pressed(KeyCode::Escape) {
         exit.send(bevy::app::AppExit);
     }
 
-    // We'll add a living cell on the point where mouse was pressed
-    if buttons.pressed(MouseButton::Left) {
-        let eguictx = eguic.ctx_mut();
-        // Skip the event if mouse is over UI element
-        if eguictx.is_pointer_over_area() {
-            return ();
+    let win = q_win.single();
+    let (camera, camera_transform) = q_camera.single();
+    // The world position of the cursor and the cell under it
+    let cursor = camera::cursor_to_world(win, camera, camera_transform).map(|position| {
+        (
+            position,
+            board_size.world_to_cell(position, settings.cell_size),
+        )
+    });
+    hovered.0 = cursor
+        .map(|(_, cell)| cell)
+        .filter(|cell| board_size.contains(*cell));
+    let symmetry_center = brush.symmetry_center.unwrap_or(board_size.center());
+
+    // Shapes are rubber-banded while the button is held and committed once it's released
+    if let Some((anchor, _)) = stroke.anchor {
+        let button = if stroke.erasing {
+            MouseButton::Right
+        } else {
+            MouseButton::Left
+        };
+        if let Some((_, cell)) = cursor {
+            stroke.last = Some(cell);
         }
+        if !buttons.pressed(button) {
+            let mode = if stroke.erasing {
+                BrushMode::Erase
+            } else {
+                brush.mode
+            };
+            let end = stroke.last.unwrap_or(anchor);
+            let cells = brush::shape(&brush, anchor, end, board_size.lattice);
+            for cell in brush::symmetric(cells, brush.symmetry, symmetry_center, board_size.lattice)
+            {
+                paint_cell(
+                    &mut universe,
+                    cell.x,
+                    cell.y,
+                    mode,
+                    brush.state,
+                    &mut stroke.inverted,
+                );
+            }
+            stroke.clear();
+        }
+        return;
+    }
 
-        let win = q_win.single();
-        if let Some(position) = win.cursor_position() {
-            // X in the texture buffer
-            let posx = (position.x / win.width() * board_size.rows as f32).round() as i32;
-            let posy = (position.y / win.height() * board_size.columns as f32).round() as i32;
-
-            let h = &board_handle.0;
-
-            if let Some(board) = images.get_mut(h) {
-                // We iterate through the square of the brush, we check if the pixel we picked is within the range of the circle around our cursor
-                for bx in -(brush.size as i32)..=brush.size as i32 {
-                    for by in -(brush.size as i32)..=brush.size as i32 {
-                        let x = posx + bx;
-                        let y = posy + by;
+    // Left click paints with the current brush mode, right click always erases
+    let erasing = buttons.pressed(MouseButton::Right);
+    if !buttons.pressed(MouseButton::Left) && !erasing {
+        // The stroke is over, cells can be inverted again
+        stroke.clear();
+        return;
+    }
+    let eguictx = eguic.ctx_mut();
+    // Skip the event if mouse is over UI element, unless it's dragging a stroke that started on the board
+    let over_ui = eguictx.is_pointer_over_area()
+        || q_interactions
+            .iter()
+            .any(|interaction| *interaction != Interaction::None);
+    if !stroke.is_active() && over_ui {
+        return;
+    }
+    let Some((position, cell)) = cursor else {
+        return;
+    };
+    // Ctrl + click picks the point strokes are mirrored around
+    if keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        if buttons.just_pressed(MouseButton::Left) {
+            brush.symmetry_center = Some(cell);
+        }
+        return;
+    }
+    // While placing turmites a left click spawns one instead of painting
+    if turmite_settings.placing && !erasing {
+        if buttons.just_pressed(MouseButton::Left) && board_size.contains(cell) {
+            turmites::spawn(&mut commands, &mut turmite_settings, cell);
+        }
+        return;
+    }
+    stroke.erasing = erasing;
 
-                        let r = (((x - posx).pow(2) + (y - posy).pow(2)) as f32).sqrt();
-                        if r <= brush.size as f32 {
-                            // this probably can be done in a more rusty way instead of just raw pointers but the borrow checker wont let you do [&mut u8; 4] obv ~
-                            if let Some(pixel) = board.get_pixel_mut(x, y) {
-                                unsafe {
-                                    *pixel[0] = settings.alive_color[0];
-                                    *pixel[1] = settings.alive_color[1];
-                                    *pixel[2] = settings.alive_color[2];
-                                    *pixel[3] = settings.alive_color[3];
-                                }
-                            };
-                        }
-                    }
-                }
+    let mode = if erasing {
+        BrushMode::Erase
+    } else {
+        brush.mode
+    };
+    match brush.tool {
+        BrushTool::Freehand | BrushTool::Spray => {
+            // Draw a line from where the cursor was on the last frame so fast strokes don't leave gaps
+            let from = stroke.last.unwrap_or(cell);
+            let mut cells = brush::shape(&brush, from, cell, board_size.lattice);
+            if brush.tool == BrushTool::Spray {
+                let mut rng = rand::thread_rng();
+                cells.retain(|_| rng.gen::<f32>() < brush.spray_density);
+            }
+            for c in brush::symmetric(cells, brush.symmetry, symmetry_center, board_size.lattice) {
+                paint_cell(
+                    &mut universe,
+                    c.x,
+                    c.y,
+                    mode,
+                    brush.state,
+                    &mut stroke.inverted,
+                );
+            }
+            stroke.last = Some(cell);
+        }
+        BrushTool::Line | BrushTool::Rectangle | BrushTool::Ellipse => {
+            stroke.anchor = Some((cell, position));
+            stroke.last = Some(cell);
+        }
+        BrushTool::Fill => {
+            // Fill once per click
+            if stroke.last.is_some() {
+                return;
+            }
+            stroke.last = Some(cell);
+            let Some(target) = universe.get(cell.x, cell.y) else {
+                return;
+            };
+            let size = IVec2::new(board_size.rows as i32, board_size.columns as i32);
+            let region =
+                brush::flood_fill(cell, size, brush.connectivity, board_size.lattice, |c| {
+                    universe.get(c.x, c.y) == Some(target)
+                });
+            for c in brush::symmetric(region, brush.symmetry, symmetry_center, board_size.lattice) {
+                paint_cell(
+                    &mut universe,
+                    c.x,
+                    c.y,
+                    mode,
+                    brush.state,
+                    &mut stroke.inverted,
+                );
             }
         }
     }
@@ -393,52 +596,67 @@ fn handle_events(
 /// UTILS
 ////////////////////////////////////////////////////////////////////////
 
-// Puts the whole board on dead
-fn reset_board(board: &mut Image, settings: &GameSettings) {
-    for i in 0..board.data.len() / 4 {
-        let c = i * 4;
-        board.data[c + 0] = settings.dead_color[0];
-        board.data[c + 1] = settings.dead_color[1];
-        board.data[c + 2] = settings.dead_color[2];
-        board.data[c + 3] = settings.dead_color[3];
-    }
+// Applies the brush mode to a single cell, painting `painted`. `inverted` holds the cells already inverted
+// during the stroke
+fn paint_cell(
+    universe: &mut Universe,
+    x: i32,
+    y: i32,
+    mode: BrushMode,
+    painted: State,
+    inverted: &mut HashSet<IVec2>,
+) {
+    let state = match mode {
+        BrushMode::Paint => painted,
+        BrushMode::Erase => State::DEAD,
+        BrushMode::Invert => {
+            if !inverted.insert(IVec2::new(x, y)) {
+                return;
+            }
+            // Dying cells count as alive
+            match universe.get(x, y) {
+                Some(State::DEAD) => painted,
+                Some(_) => State::DEAD,
+                None => return,
+            }
+        }
+    };
+    universe.set(x, y, state);
 }
-// Seeds the state of the board (for now just a simple 50%)
-fn seed(board: &mut Image, settings: &GameSettings) {
+// Seeds the state of the board, one dimensional rules only start from the first row
+fn seed(universe: &mut Universe, settings: &GameSettings, rule: &Rule) {
+    let rows = if rule.is_one_dimensional() {
+        1
+    } else {
+        settings.board_height as i32
+    };
     // match settings.seed
 
-    let gun_to_buff_values = |settings: &GameSettings, gun: &Vec<Vec<i32>>| {
-        let mut res: Vec<Vec<u8>> = vec![];
-        for i in 0..gun.len() {
-            let row = &gun[i];
-            let mut r = vec![];
-            for j in 0..row.len() {
-                if row[j] == 1 {
-                    r.push(settings.alive_color[0]);
-                    r.push(settings.alive_color[1]);
-                    r.push(settings.alive_color[2]);
-                    r.push(settings.alive_color[3]);
-                } else {
-                    r.push(settings.dead_color[0]);
-                    r.push(settings.dead_color[1]);
-                    r.push(settings.dead_color[2]);
-                    r.push(settings.dead_color[3]);
+    // Places a pattern of 1s and 0s in the centre of the board
+    let place_pattern = |universe: &mut Universe, pattern: &Vec<Vec<i32>>| {
+        let x_start = settings.board_width as i32 / 2 - pattern[0].len() as i32 / 2;
+        let y_start = settings.board_height as i32 / 2 - pattern.len() as i32 / 2;
+        for (y, row) in pattern.iter().enumerate() {
+            for (x, cell) in row.iter().enumerate() {
+                if *cell == 1 {
+                    universe.set(x_start + x as i32, y_start + y as i32, State::ALIVE);
                 }
             }
-            res.push(r);
         }
-        return res;
     };
     match settings.seed {
         Seed::Random => {
+            // Big boards only get a soup of at most `MAX_CELLS` cells in their middle
+            let side = (MAX_CELLS as f32).sqrt() as i32;
+            let (width, height) = (settings.board_width as i32, rows);
+            let first = (IVec2::new(width, height) - side).max(IVec2::ZERO) / 2;
             let mut rng = rand::thread_rng();
-            for i in 0..(board.data.len() / 4) {
-                let rand: f32 = rng.gen();
-                if rand >= 0.5 {
-                    board.data[i * 4 + 0] = settings.alive_color[0];
-                    board.data[i * 4 + 1] = settings.alive_color[1];
-                    board.data[i * 4 + 2] = settings.alive_color[2];
-                    board.data[i * 4 + 3] = settings.alive_color[3];
+            for y in first.y..(first.y + side).min(height) {
+                for x in first.x..(first.x + side).min(width) {
+                    let rand: f32 = rng.gen();
+                    if rand >= 0.5 {
+                        universe.set(x, y, State::ALIVE);
+                    }
                 }
             }
         }
@@ -458,35 +676,7 @@ fn seed(board: &mut Image, settings: &GameSettings) {
                 vec![0,0,0,0,0,0,0,0,0,0,0,0,1,1,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],
             ];
 
-            let board_size = board.size();
-
-            let values = gun_to_buff_values(settings, &glider_gun);
-
-            #[rustfmt::skip]
-            let offset_y = if glider_gun.len() as f32 % 2. != 0. { 1 } else { 0 };
-            #[rustfmt::skip]
-            let offset_x = if glider_gun[0].len() as f32 % 2. != 0. { 1 } else { 0 };
-
-            let center = board_size / 2.;
-            let x_start = center.x as usize - glider_gun[0].len() / 2;
-            let x_end = (center.x as usize + glider_gun[0].len() / 2) + offset_x;
-            let y_start = center.y as usize - glider_gun.len() / 2;
-            let y_end = (center.y as usize + glider_gun.len() / 2) + offset_y;
-
-            let mut yy = 0;
-            let mut xx = 0;
-            for i in y_start..y_end {
-                for j in x_start..x_end {
-                    let offset = (i * board_size.x as usize * 4) + j * 4;
-                    board.data[offset + 0] = values[yy][xx + 0];
-                    board.data[offset + 1] = values[yy][xx + 1];
-                    board.data[offset + 2] = values[yy][xx + 2];
-                    board.data[offset + 3] = values[yy][xx + 3];
-                    xx += 4;
-                }
-                xx = 0;
-                yy += 1;
-            }
+            place_pattern(universe, &glider_gun);
         }
         Seed::SimkinGliderGun => {
             #[rustfmt::skip]
@@ -513,137 +703,49 @@ fn seed(board: &mut Image, settings: &GameSettings) {
                 vec![0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,1,1,0,1,0,0,0,0,0],
             ];
 
-            let board_size = board.size();
-
-            let values = gun_to_buff_values(settings, &glider_gun);
-
-            #[rustfmt::skip]
-            let offset_y = if glider_gun.len() as f32 % 2. != 0. { 1 } else { 0 };
-            #[rustfmt::skip]
-            let offset_x = if glider_gun[0].len() as f32 % 2. != 0. { 1 } else { 0 };
-
-            let center = board_size / 2.;
-            let x_start = center.x as usize - glider_gun[0].len() / 2;
-            let x_end = (center.x as usize + glider_gun[0].len() / 2) + offset_x;
-            let y_start = center.y as usize - glider_gun.len() / 2;
-            let y_end = (center.y as usize + glider_gun.len() / 2) + offset_y;
-
-            let mut yy = 0;
-            let mut xx = 0;
-            for i in y_start..y_end {
-                for j in x_start..x_end {
-                    let offset = (i * board_size.x as usize * 4) + j * 4;
-                    board.data[offset + 0] = values[yy][xx + 0];
-                    board.data[offset + 1] = values[yy][xx + 1];
-                    board.data[offset + 2] = values[yy][xx + 2];
-                    board.data[offset + 3] = values[yy][xx + 3];
-                    xx += 4;
-                }
-                xx = 0;
-                yy += 1;
-            }
+            place_pattern(universe, &glider_gun);
+        }
+        Seed::SingleCell => {
+            let y = if rows == 1 { 0 } else { rows / 2 };
+            universe.set(settings.board_width as i32 / 2, y, State::ALIVE);
         }
 
         _ => {}
     }
 }
-// Looks at a cell at a pixel in the image and determines if it's alive
-fn cell_state(image: &Image, x: i32, y: i32, settings: &GameSettings) -> State {
-    // https://en.wikipedia.org/wiki/Conway%27s_Game_of_Life#Rules
-
-    let mut neighbours_alive = 0;
-
-    // neighbours x
-    for nx in -1..=1 {
-        // neighbors y
-        for ny in -1..=1 {
-            // if its the center one (the cell we're determining)
-            if nx == 0 && ny == 0 {
-                continue;
-            }
-
-            let n = image.get_pixel(x + nx, y + ny);
-            if let Some(n_cell) = n {
-                match State::cell_state(&n_cell, settings) {
-                    State::ALIVE => neighbours_alive += 1,
-                    State::DEAD => {}
-                }
-            }
-        }
+// Copies the cells of a pattern that aren't dead, centred on `center`. Hexagonal patterns are sheared
+// (see `lattice::from_sheared`), they start on an even row. Triangular ones start with a cell pointing up
+fn place_pattern(universe: &mut Universe, pattern: &Pattern, center: IVec2, lattice: Lattice) {
+    let mut start = center - IVec2::new(pattern.width as i32, pattern.height as i32) / 2;
+    if lattice == Lattice::Hexagonal {
+        // Unshearing moves the bottom rows left by half the height
+        start += IVec2::new(pattern.height as i32 / 4, 0);
+        start.y -= start.y.rem_euclid(2);
     }
-
-    let cell_state = State::cell_state(&image.get_pixel(x, y).unwrap(), settings);
-
-    match cell_state {
-        State::ALIVE => {
-            if neighbours_alive < 2 {
-                return State::DEAD;
+    if lattice == Lattice::Triangular && !lattice::points_up(start) {
+        // Triangles keep pointing the same way
+        start.x -= 1;
+    }
+    for y in 0..pattern.height {
+        for x in 0..pattern.width {
+            let state = pattern.get(x, y);
+            if state == State::DEAD {
+                continue;
             }
-            if neighbours_alive == 2 || neighbours_alive == 3 {
-                return State::ALIVE;
-            } else {
-                return State::DEAD;
-            };
-        }
-        State::DEAD => {
-            if neighbours_alive == 3 {
-                return State::ALIVE;
-            } else {
-                return State::DEAD;
+            let mut cell = IVec2::new(x as i32, y as i32);
+            if lattice == Lattice::Hexagonal {
+                cell = lattice::from_sheared(cell);
             }
+            universe.set(start.x + cell.x, start.y + cell.y, state);
         }
     }
 }
-
-trait Pixel {
-    fn get_pixel(&self, x: i32, y: i32) -> Option<[&u8; 4]>;
-    fn get_pixel_mut(&mut self, x: i32, y: i32) -> Option<[*mut u8; 4]>;
-}
-impl Pixel for Image {
-    fn get_pixel(&self, x: i32, y: i32) -> Option<[&u8; 4]> {
-        let size = self.size();
-
-        if x >= size.x as i32 || x < 0 as i32 || y >= size.y as i32 || y < 0 {
-            return None;
-        }
-
-        let pos = (y * size.x as i32 + x) * 4;
-
-        let r = &self.data[(pos + 0) as usize];
-        let g = &self.data[(pos + 1) as usize];
-        let b = &self.data[(pos + 2) as usize];
-        let a = &self.data[(pos + 3) as usize];
-
-        return Some([r, g, b, a]);
-    }
-    fn get_pixel_mut(&mut self, x: i32, y: i32) -> Option<[*mut u8; 4]> {
-        let size = self.size();
-
-        if x >= size.x as i32 || x < 0 as i32 || y >= size.y as i32 || y < 0 {
-            return None;
-        }
-
-        let pos = (y * size.x as i32 + x) * 4;
-
-        let r = unsafe { self.data.as_mut_ptr().add(pos as usize + 0) };
-        let g = unsafe { self.data.as_mut_ptr().add(pos as usize + 1) };
-        let b = unsafe { self.data.as_mut_ptr().add(pos as usize + 2) };
-        let a = unsafe { self.data.as_mut_ptr().add(pos as usize + 3) };
-
-        return Some([r, g, b, a]);
-    }
-}
-impl State {
-    fn cell_state(data: &[&u8; 4], settings: &GameSettings) -> State {
-        // cells are red
-        if *data[0] == settings.alive_color[0]
-            && *data[1] == settings.alive_color[1]
-            && *data[2] == settings.alive_color[2]
-            && *data[3] == settings.alive_color[3]
-        {
-            State::ALIVE
-        } else {
-            State::DEAD
-        }
-    }
+// Everything the cell inspector shows about a cell
+pub fn inspect(universe: &Universe, rule: &Rule, cell: IVec2) -> Option<CellInfo> {
+    Some(CellInfo {
+        state: universe.get(cell.x, cell.y)?,
+        neighbours: universe.rule_neighbours(rule, cell.x, cell.y),
+        next: universe.next_state(rule, cell.x, cell.y),
+        age: universe.age(cell.x, cell.y)?,
+    })
 }
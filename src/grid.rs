@@ -0,0 +1,259 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_egui::{egui, EguiContexts};
+
+use crate::camera::MainCamera;
+use crate::game_of_life::{BoardSize, GameSettings};
+use crate::lattice::Lattice;
+
+/**
+ *  Grid lines between the cells and rulers along the top and left edges of the window.
+ *  Hexagonal and triangular cells have no lines running across the board, each one is outlined instead.
+ *  Coordinates shown on the rulers have the origin at the centre of the board and grow right and up.
+ */
+
+pub const RULER_WIDTH: f32 = 24.; // Pixels
+const MIN_TICK_SPACING: f32 = 60.; // Pixels between the labels of the rulers
+const MIN_CELL_PIXELS: f32 = 4.; // Below this size on screen the grid fades out
+const MAX_OUTLINED_CELLS: i32 = 20_000; // Cells that aren't square are outlined one by one up to this many
+
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct GridSettings {
+    pub show_grid: bool,
+    // Every how many cells there's a major line
+    pub major_every: u32,
+    pub show_rulers: bool,
+}
+impl Default for GridSettings {
+    fn default() -> Self {
+        GridSettings {
+            show_grid: false,
+            major_every: 10,
+            show_rulers: false,
+        }
+    }
+}
+
+// The part of the board the camera sees, in cells (the first and last columns and rows, which may be off the board)
+pub struct View {
+    // On screen size of a cell in pixels
+    pub cell_pixels: f32,
+    pub min: IVec2,
+    pub max: IVec2,
+}
+
+////////////////////////////////////////////////////////////////////////
+/// SYSTEMS
+////////////////////////////////////////////////////////////////////////
+
+pub fn draw_grid(
+    grid: Res<GridSettings>,
+    mut gizmos: Gizmos,
+    q_camera: Query<(&Transform, &OrthographicProjection), With<MainCamera>>,
+    q_win: Query<&Window, With<PrimaryWindow>>,
+    board_size: Res<BoardSize>,
+    settings: Res<GameSettings>,
+) {
+    if !grid.show_grid {
+        return;
+    }
+    let (transform, projection) = q_camera.single();
+    let view = visible_cells(
+        transform,
+        projection,
+        q_win.single(),
+        &board_size,
+        &settings,
+    );
+
+    // Fade in the lines as the cells get bigger on screen
+    let fade = |pixels: f32| ((pixels - MIN_CELL_PIXELS) / (MIN_CELL_PIXELS * 2.)).clamp(0., 1.);
+    let minor_alpha = fade(view.cell_pixels) * 0.25;
+    let major_alpha = fade(view.cell_pixels * grid.major_every as f32) * 0.5;
+    if major_alpha <= 0. {
+        return;
+    }
+
+    // Only draw the lines that are on the board and on screen
+    let min = view.min.max(IVec2::ZERO);
+    let max = (view.max + 1).min(IVec2::new(
+        board_size.rows as i32,
+        board_size.columns as i32,
+    ));
+    if board_size.lattice != Lattice::Square {
+        // The outline of every cell, there are no lines to tell apart
+        let cells = (max - min).max(IVec2::ZERO);
+        if minor_alpha <= 0. || cells.x * cells.y > MAX_OUTLINED_CELLS {
+            return;
+        }
+        for y in min.y..max.y {
+            for x in min.x..max.x {
+                let outline = board_size.cell_outline(IVec2::new(x, y), settings.cell_size);
+                let first = outline[0];
+                gizmos.linestrip_2d(
+                    outline.into_iter().chain([first]),
+                    Color::rgba(1., 1., 1., minor_alpha),
+                );
+            }
+        }
+        return;
+    }
+    let cell_size = settings.cell_size as f32;
+    let half = board_size.world_size(settings.cell_size) / 2.;
+    let top = half.y - min.y as f32 * cell_size;
+    let bottom = half.y - max.y as f32 * cell_size;
+    let left = min.x as f32 * cell_size - half.x;
+    let right = max.x as f32 * cell_size - half.x;
+    let center = board_size.center();
+
+    for x in min.x..=max.x {
+        let major = (x - center.x) % grid.major_every as i32 == 0;
+        let alpha = if major { major_alpha } else { minor_alpha };
+        if alpha > 0. {
+            let wx = x as f32 * cell_size - half.x;
+            gizmos.line_2d(
+                Vec2::new(wx, top),
+                Vec2::new(wx, bottom),
+                Color::rgba(1., 1., 1., alpha),
+            );
+        }
+    }
+    for y in min.y..=max.y {
+        let major = (y - center.y) % grid.major_every as i32 == 0;
+        let alpha = if major { major_alpha } else { minor_alpha };
+        if alpha > 0. {
+            let wy = half.y - y as f32 * cell_size;
+            gizmos.line_2d(
+                Vec2::new(left, wy),
+                Vec2::new(right, wy),
+                Color::rgba(1., 1., 1., alpha),
+            );
+        }
+    }
+}
+
+pub fn draw_rulers(
+    grid: Res<GridSettings>,
+    mut eguic: EguiContexts,
+    q_camera: Query<(&Transform, &OrthographicProjection), With<MainCamera>>,
+    q_win: Query<&Window, With<PrimaryWindow>>,
+    board_size: Res<BoardSize>,
+    settings: Res<GameSettings>,
+) {
+    if !grid.show_rulers {
+        return;
+    }
+    let (transform, projection) = q_camera.single();
+    let win = q_win.single();
+    let view = visible_cells(transform, projection, win, &board_size, &settings);
+    let center = board_size.center();
+    // Screen position of the left and top edges of a column and row
+    let cell_size = settings.cell_size as f32;
+    let half = board_size.world_size(settings.cell_size) / 2.;
+    let camera = transform.translation.truncate();
+    let column_to_screen =
+        |x: i32| (x as f32 * cell_size - half.x - camera.x) / projection.scale + win.width() / 2.;
+    let row_to_screen = |y: i32| {
+        (camera.y - (half.y - y as f32 * cell_size)) / projection.scale + win.height() / 2.
+    };
+    let step = tick_step(view.cell_pixels);
+
+    let ctx = eguic.ctx_mut();
+    let painter = ctx.layer_painter(egui::LayerId::new(
+        egui::Order::Background,
+        egui::Id::new("rulers"),
+    ));
+    let background = egui::Color32::from_black_alpha(180);
+    let text_color = egui::Color32::LIGHT_GRAY;
+    let font = egui::FontId::monospace(10.);
+    painter.rect_filled(
+        egui::Rect::from_min_size(egui::pos2(0., 0.), egui::vec2(win.width(), RULER_WIDTH)),
+        0.,
+        background,
+    );
+    painter.rect_filled(
+        egui::Rect::from_min_size(egui::pos2(0., 0.), egui::vec2(RULER_WIDTH, win.height())),
+        0.,
+        background,
+    );
+
+    // Ticks are placed on multiples of the step counting from the centre of the board
+    let first = (view.min.x - center.x).div_euclid(step) * step;
+    for coordinate in (first..=view.max.x - center.x).step_by(step as usize) {
+        // Label the middle of the cell
+        let x = column_to_screen(coordinate + center.x) + view.cell_pixels / 2.;
+        if x < RULER_WIDTH {
+            continue;
+        }
+        painter.line_segment(
+            [egui::pos2(x, RULER_WIDTH - 4.), egui::pos2(x, RULER_WIDTH)],
+            egui::Stroke::new(1., text_color),
+        );
+        painter.text(
+            egui::pos2(x + 2., 1.),
+            egui::Align2::LEFT_TOP,
+            coordinate.to_string(),
+            font.clone(),
+            text_color,
+        );
+    }
+    // Rows grow downwards on the board but coordinates grow upwards
+    let first = (center.y - view.max.y).div_euclid(step) * step;
+    for coordinate in (first..=center.y - view.min.y).step_by(step as usize) {
+        let y = row_to_screen(center.y - coordinate) + view.cell_pixels / 2.;
+        if y < RULER_WIDTH {
+            continue;
+        }
+        painter.line_segment(
+            [egui::pos2(RULER_WIDTH - 4., y), egui::pos2(RULER_WIDTH, y)],
+            egui::Stroke::new(1., text_color),
+        );
+        painter.text(
+            egui::pos2(1., y - 2.),
+            egui::Align2::LEFT_BOTTOM,
+            coordinate.to_string(),
+            font.clone(),
+            text_color,
+        );
+    }
+}
+
+////////////////////////////////////////////////////////////////////////
+/// UTILS
+////////////////////////////////////////////////////////////////////////
+
+pub fn visible_cells(
+    transform: &Transform,
+    projection: &OrthographicProjection,
+    win: &Window,
+    board_size: &BoardSize,
+    settings: &GameSettings,
+) -> View {
+    let half_view = Vec2::new(win.width(), win.height()) / 2. * projection.scale;
+    let camera = transform.translation.truncate();
+    let top_left = Vec2::new(camera.x - half_view.x, camera.y + half_view.y);
+    let bottom_right = Vec2::new(camera.x + half_view.x, camera.y - half_view.y);
+    View {
+        cell_pixels: settings.cell_size as f32 / projection.scale,
+        min: board_size.world_to_cell(top_left, settings.cell_size),
+        max: board_size.world_to_cell(bottom_right, settings.cell_size),
+    }
+}
+
+// Cells between two labels of the ruler, 1, 2 or 5 times a power of ten so they're at least `MIN_TICK_SPACING` apart
+fn tick_step(cell_pixels: f32) -> i32 {
+    let mut step = 1;
+    loop {
+        for multiplier in [1, 2, 5] {
+            if (step * multiplier) as f32 * cell_pixels >= MIN_TICK_SPACING {
+                return step * multiplier;
+            }
+        }
+        step *= 10;
+    }
+}
+
+// The board cell of a coordinate shown on the rulers
+pub fn coordinate_to_cell(coordinate: IVec2, board_size: &BoardSize) -> IVec2 {
+    let center = board_size.center();
+    IVec2::new(center.x + coordinate.x, center.y - coordinate.y)
+}
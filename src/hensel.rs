@@ -0,0 +1,308 @@
+use crate::rules::Neighbourhood;
+
+/**
+ *  Hensel's notation for isotropic non-totalistic rules, like B2ae3/S23-q. Each letter after a number of living
+ *  neighbours is one arrangement of them, the same up to rotations and reflections
+ *  (https://conwaylife.com/wiki/Isotropic_non-totalistic_rule). A number alone is every arrangement,
+ *  a minus keeps the ones whose letter isn't listed.
+ *  Arrangements are masks of the 3 x 3 square row by row, the cell itself is bit 4. On the hexagonal lattice
+ *  they're masks of the 6 neighbours clockwise from the top right with the cell on bit 6, and 2 to 4 neighbours
+ *  are told apart by Golly's letters o, m and p (touching, one apart and opposite for 2 neighbours).
+ */
+
+// Where the cell and its neighbours are in the masks of a neighbourhood, with the letters of 1 living neighbour
+// and more up to half of them, and one arrangement of each in the same order. The letters of more living
+// neighbours are the ones of the dead neighbours
+struct Layout {
+    cell: u16,
+    neighbours: u16,
+    letters: &'static [&'static str],
+    arrangements: &'static [&'static [u16]],
+    // Every arrangement that's the same up to rotations and reflections
+    symmetries: fn(u16) -> Vec<u16>,
+}
+
+impl Layout {
+    // Masks of the cell and its neighbours
+    fn masks(&self) -> u16 {
+        (self.cell | self.neighbours) + 1
+    }
+}
+
+const MOORE: Layout = Layout {
+    cell: 1 << 4,
+    neighbours: 0b111_101_111,
+    letters: &["ce", "ceaikn", "ceaiknjqry", "ceaiknjqrtwyz"],
+    arrangements: &[
+        &[1, 2],
+        &[5, 10, 3, 40, 33, 68],
+        &[69, 42, 11, 7, 98, 13, 14, 70, 41, 97],
+        &[325, 170, 15, 45, 99, 71, 106, 102, 43, 101, 105, 78, 108],
+    ],
+    symmetries: square_symmetries,
+};
+const HEXAGONAL: Layout = Layout {
+    cell: 1 << 6,
+    neighbours: 0b111_111,
+    letters: &["", "omp", "omp"],
+    arrangements: &[&[], &[0b11, 0b101, 0b1001], &[0b111, 0b1011, 0b10101]],
+    symmetries: hexagon_symmetries,
+};
+
+////////////////////////////////////////////////////////////////////////
+/// UTILS
+////////////////////////////////////////////////////////////////////////
+
+// Which arrangements of living neighbours a part of a rule like 2ae3 or 23-q has, indexed by their mask
+pub fn parse(text: &str, neighbourhood: Neighbourhood) -> Result<Vec<bool>, String> {
+    let layout = layout(neighbourhood);
+    let most = layout.neighbours.count_ones();
+    let mut arrangements = vec![false; layout.masks() as usize];
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let count = c
+            .to_digit(10)
+            .filter(|&count| count <= most)
+            .ok_or_else(|| format!("\"{}\" isn't a neighbour count from 0 to {}", c, most))?;
+        let negated = chars.next_if_eq(&'-').is_some();
+        let mut picked = String::new();
+        while let Some(letter) = chars.next_if(char::is_ascii_alphabetic) {
+            picked.push(letter.to_ascii_lowercase());
+        }
+        let letters = letters(layout, count);
+        if let Some(letter) = picked
+            .chars()
+            .find(|l| !letters.iter().any(|(letter, _)| letter == l))
+        {
+            return Err(format!("{}{} isn't an arrangement", count, letter));
+        }
+        if negated && picked.is_empty() {
+            return Err(format!("Letters are missing after {}-", count));
+        }
+        for mask in neighbour_masks(layout, count) {
+            let listed = letter_of(layout, mask).is_some_and(|letter| picked.contains(letter));
+            if picked.is_empty() || listed != negated {
+                arrangements[mask as usize] = true;
+            }
+        }
+    }
+    Ok(arrangements)
+}
+// Writes the arrangements the way `parse` reads them, with a minus when it's shorter
+pub fn write(arrangements: &[bool], neighbourhood: Neighbourhood) -> String {
+    let layout = layout(neighbourhood);
+    let mut text = String::new();
+    for count in 0..=layout.neighbours.count_ones() {
+        let letters = letters(layout, count);
+        let included: String = letters
+            .iter()
+            .filter(|(_, arrangement)| arrangements[*arrangement as usize])
+            .map(|(letter, _)| *letter)
+            .collect();
+        let excluded: String = letters
+            .iter()
+            .map(|(letter, _)| *letter)
+            .filter(|letter| !included.contains(*letter))
+            .collect();
+        if letters.is_empty() {
+            // No neighbours or all of them can only be arranged one way
+            if neighbour_masks(layout, count).any(|mask| arrangements[mask as usize]) {
+                text.push_str(&count.to_string());
+            }
+        } else if !included.is_empty() {
+            let letters = if excluded.is_empty() {
+                String::new()
+            } else if included.len() <= excluded.len() {
+                included
+            } else {
+                format!("-{}", excluded)
+            };
+            text.push_str(&format!("{}{}", count, letters));
+        }
+    }
+    text
+}
+// The bit of the cell itself in the masks of a neighbourhood
+pub fn cell(neighbourhood: Neighbourhood) -> usize {
+    layout(neighbourhood).cell as usize
+}
+fn layout(neighbourhood: Neighbourhood) -> &'static Layout {
+    match neighbourhood {
+        Neighbourhood::Hexagonal => &HEXAGONAL,
+        _ => &MOORE,
+    }
+}
+// The letters of `count` living neighbours with one arrangement of each
+fn letters(layout: &Layout, count: u32) -> Vec<(char, u16)> {
+    let most = layout.neighbours.count_ones();
+    match count {
+        0 => vec![],
+        _ if count as usize <= layout.letters.len() => layout.letters[count as usize - 1]
+            .chars()
+            .zip(layout.arrangements[count as usize - 1].iter().copied())
+            .collect(),
+        // The cells that aren't alive in the arrangements of `most` - count
+        _ if count < most => letters(layout, most - count)
+            .into_iter()
+            .map(|(letter, arrangement)| (letter, arrangement ^ layout.neighbours))
+            .collect(),
+        _ => vec![],
+    }
+}
+// The letter of an arrangement of living neighbours
+fn letter_of(layout: &Layout, mask: u16) -> Option<char> {
+    letters(layout, mask.count_ones())
+        .into_iter()
+        .find(|(_, arrangement)| (layout.symmetries)(*arrangement).contains(&mask))
+        .map(|(letter, _)| letter)
+}
+// Every arrangement of `count` living neighbours, without the cell itself
+fn neighbour_masks(layout: &Layout, count: u32) -> impl Iterator<Item = u16> + '_ {
+    (0..layout.masks()).filter(move |mask| mask & layout.cell == 0 && mask.count_ones() == count)
+}
+// The arrangement turned by quarter turns and mirrored
+fn square_symmetries(arrangement: u16) -> Vec<u16> {
+    let transform = |mask: u16, f: fn(u16, u16) -> (u16, u16)| {
+        (0..9)
+            .filter(|bit| mask & (1 << bit) != 0)
+            .map(|bit| {
+                let (x, y) = f(bit % 3, bit / 3);
+                1 << (y * 3 + x)
+            })
+            .sum::<u16>()
+    };
+    let rotate = |mask| transform(mask, |x, y| (2 - y, x));
+    let mirror = |mask| transform(mask, |x, y| (2 - x, y));
+    let mut all = vec![];
+    let mut mask = arrangement;
+    for _ in 0..4 {
+        all.push(mask);
+        all.push(mirror(mask));
+        mask = rotate(mask);
+    }
+    all
+}
+// The arrangement turned by sixth turns around the ring of neighbours and mirrored
+fn hexagon_symmetries(arrangement: u16) -> Vec<u16> {
+    let rotate = |mask: u16| (mask << 1 | mask >> 5) & HEXAGONAL.neighbours;
+    let mirror = |mask: u16| {
+        (0..6)
+            .filter(|bit| mask & (1 << bit) != 0)
+            .map(|bit| 1 << ((6 - bit) % 6))
+            .sum::<u16>()
+    };
+    let mut all = vec![];
+    let mut mask = arrangement;
+    for _ in 0..6 {
+        all.push(mask);
+        all.push(mirror(mask));
+        mask = rotate(mask);
+    }
+    all
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The mask of the cells at (x, y) in the 3 x 3 square
+    fn square(cells: &[(u16, u16)]) -> u16 {
+        cells.iter().map(|(x, y)| 1 << (y * 3 + x)).sum()
+    }
+
+    #[test]
+    fn letters_split_every_arrangement_once() {
+        // 51 arrangements on the square, 13 on the hexagon, up to rotations and reflections
+        for (layout, classes) in [(&MOORE, 51), (&HEXAGONAL, 13)] {
+            let most = layout.neighbours.count_ones();
+            let mut total = 0;
+            for count in 0..=most {
+                let letters = letters(layout, count);
+                let masks: Vec<u16> = neighbour_masks(layout, count).collect();
+                if letters.is_empty() {
+                    total += 1;
+                    continue;
+                }
+                total += letters.len();
+                for mask in masks {
+                    let matching = letters
+                        .iter()
+                        .filter(|(_, arrangement)| {
+                            (layout.symmetries)(*arrangement).contains(&mask)
+                        })
+                        .count();
+                    assert_eq!(matching, 1, "{} neighbours, mask {:b}", count, mask);
+                }
+            }
+            assert_eq!(total, classes);
+        }
+    }
+
+    #[test]
+    fn square_letters_are_hensels() {
+        let letter = |cells: &[(u16, u16)]| letter_of(&MOORE, square(cells));
+        assert_eq!(letter(&[(2, 2)]), Some('c'));
+        assert_eq!(letter(&[(0, 1)]), Some('e'));
+        assert_eq!(letter(&[(0, 0), (1, 0)]), Some('a'));
+        assert_eq!(letter(&[(0, 0), (2, 0)]), Some('c'));
+        assert_eq!(letter(&[(1, 0), (2, 1)]), Some('e'));
+        assert_eq!(letter(&[(1, 0), (1, 2)]), Some('i'));
+        assert_eq!(letter(&[(0, 0), (2, 1)]), Some('k'));
+        assert_eq!(letter(&[(0, 0), (2, 2)]), Some('n'));
+        // A line along a side, three edges and a corner with the edges around it
+        assert_eq!(letter(&[(0, 0), (1, 0), (2, 0)]), Some('i'));
+        assert_eq!(letter(&[(0, 1), (1, 0), (2, 1)]), Some('e'));
+        assert_eq!(letter(&[(0, 0), (1, 0), (0, 1)]), Some('a'));
+        // The four corners and the four edges
+        assert_eq!(letter(&[(0, 0), (2, 0), (0, 2), (2, 2)]), Some('c'));
+        assert_eq!(letter(&[(1, 0), (0, 1), (2, 1), (1, 2)]), Some('e'));
+        // Seven neighbours are named after the dead one
+        assert_eq!(
+            letter(&[(0, 0), (1, 0), (2, 0), (0, 1), (2, 1), (0, 2), (1, 2)]),
+            Some('c')
+        );
+    }
+
+    #[test]
+    fn hexagon_letters_are_gollys() {
+        let letter = |mask: u16| letter_of(&HEXAGONAL, mask);
+        // Touching, one apart and opposite
+        assert_eq!(letter(0b11), Some('o'));
+        assert_eq!(letter(0b100001), Some('o'));
+        assert_eq!(letter(0b10100), Some('m'));
+        assert_eq!(letter(0b1001), Some('p'));
+        assert_eq!(letter(0b111), Some('o'));
+        assert_eq!(letter(0b1011), Some('m'));
+        assert_eq!(letter(0b10101), Some('p'));
+        // Four neighbours are named after the two dead ones
+        assert_eq!(letter(0b111100), Some('o'));
+        assert_eq!(letter(0b110110), Some('p'));
+        assert_eq!(letter(1), None);
+    }
+
+    #[test]
+    fn parses_and_writes_arrangements() {
+        let two_a = parse("2a", Neighbourhood::Moore).unwrap();
+        let all_but_a = parse("2-a", Neighbourhood::Moore).unwrap();
+        let two = parse("2", Neighbourhood::Moore).unwrap();
+        for mask in neighbour_masks(&MOORE, 2) {
+            let mask = mask as usize;
+            assert!(two[mask]);
+            assert_ne!(two_a[mask], all_but_a[mask]);
+        }
+        assert!(two_a[square(&[(1, 0), (2, 0)]) as usize]);
+        for (text, neighbourhood) in [
+            ("2-a", Neighbourhood::Moore),
+            ("1e2ai3", Neighbourhood::Moore),
+            ("4-eq", Neighbourhood::Moore),
+            ("2o3m4", Neighbourhood::Hexagonal),
+        ] {
+            let arrangements = parse(text, neighbourhood).unwrap();
+            assert_eq!(write(&arrangements, neighbourhood), text);
+        }
+        assert!(parse("2x", Neighbourhood::Moore).is_err());
+        assert!(parse("2-", Neighbourhood::Moore).is_err());
+        assert!(parse("7", Neighbourhood::Hexagonal).is_err());
+        assert!(parse("2c", Neighbourhood::Hexagonal).is_err());
+    }
+}
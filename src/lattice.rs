@@ -0,0 +1,179 @@
+use bevy::prelude::*;
+
+/**
+ *  How the cells are laid out on screen. The universe is always rows and columns of cells, the lattice only
+ *  changes where a cell is drawn and which cells touch it. On the hexagonal lattice every odd row is shifted
+ *  half a cell to the right, so a cell touches two cells of the row above and two of the row below.
+ *  The cell under a point is the one whose centre is the nearest, that's also what gives the hexagons their shape.
+ *  Golly writes hexagonal patterns sheared instead, each row half a cell left of the one above (see `from_sheared`).
+ *  Triangular cells are twice as wide as they're high and overlap half of the cells on each side of them,
+ *  they point up when x + y is even and down when it's odd.
+ */
+
+// Corners of a hexagonal cell around its centre in cells, clockwise from the top. The sides are halfway
+// to the centres of the neighbours, one cell away in the same row and 1.12 in the rows above and below
+const HEXAGON: [Vec2; 6] = [
+    Vec2::new(0., -0.625),
+    Vec2::new(0.5, -0.375),
+    Vec2::new(0.5, 0.375),
+    Vec2::new(0., 0.625),
+    Vec2::new(-0.5, 0.375),
+    Vec2::new(-0.5, -0.375),
+];
+// Radius of the circle inside a triangular cell
+const TRIANGLE_INRADIUS: f32 = std::f32::consts::SQRT_2 - 1.;
+const SQUARE: [Vec2; 4] = [
+    Vec2::new(-0.5, -0.5),
+    Vec2::new(0.5, -0.5),
+    Vec2::new(0.5, 0.5),
+    Vec2::new(-0.5, 0.5),
+];
+
+#[derive(PartialEq, Debug, Copy, Clone, Default)]
+pub enum Lattice {
+    #[default]
+    Square,
+    Hexagonal,
+    Triangular,
+}
+
+impl Lattice {
+    // The centre of a cell in cells from the top left corner of the board, y grows downwards
+    pub fn centre(&self, cell: IVec2) -> Vec2 {
+        let centre = cell.as_vec2() + 0.5;
+        match self {
+            Lattice::Square => centre,
+            Lattice::Hexagonal => centre + Vec2::new(row_shift(cell.y), 0.),
+            // The centre of the circle inside, above the base by its radius
+            Lattice::Triangular if points_up(cell) => {
+                Vec2::new(centre.x, cell.y as f32 + 1. - TRIANGLE_INRADIUS)
+            }
+            Lattice::Triangular => Vec2::new(centre.x, cell.y as f32 + TRIANGLE_INRADIUS),
+        }
+    }
+    // The cell a point is in
+    pub fn cell_at(&self, position: Vec2) -> IVec2 {
+        let row = position.y.floor() as i32;
+        match self {
+            Lattice::Square => position.floor().as_ivec2(),
+            // A hexagon pokes a quarter of a cell into the rows above and below
+            Lattice::Hexagonal => (row - 1..=row + 1)
+                .map(|y| IVec2::new((position.x - row_shift(y)).floor() as i32, y))
+                .min_by(|a, b| {
+                    let distance = |cell: &IVec2| self.centre(*cell).distance_squared(position);
+                    distance(a).total_cmp(&distance(b))
+                })
+                .unwrap(),
+            // The triangle of this column or of the ones on its sides the point is the deepest in
+            Lattice::Triangular => {
+                let column = position.x.floor() as i32;
+                (column - 1..=column + 1)
+                    .map(|x| IVec2::new(x, row))
+                    .max_by(|a, b| {
+                        triangle_depth(*a, position).total_cmp(&triangle_depth(*b, position))
+                    })
+                    .unwrap()
+            }
+        }
+    }
+    // The corners of a cell in order around it
+    pub fn outline(&self, cell: IVec2) -> Vec<Vec2> {
+        if *self == Lattice::Triangular {
+            let (x, y) = (cell.x as f32, cell.y as f32);
+            let (apex, base) = if points_up(cell) {
+                (y, y + 1.)
+            } else {
+                (y + 1., y)
+            };
+            return vec![
+                Vec2::new(x + 0.5, apex),
+                Vec2::new(x + 1.5, base),
+                Vec2::new(x - 0.5, base),
+            ];
+        }
+        let corners: &[Vec2] = match self {
+            Lattice::Hexagonal => &HEXAGON,
+            _ => &SQUARE,
+        };
+        let centre = self.centre(cell);
+        corners.iter().map(|corner| centre + *corner).collect()
+    }
+    // The cells across the sides of a cell, side i goes from corner i to corner i + 1 of its outline
+    pub fn sides(&self, cell: IVec2) -> Vec<IVec2> {
+        match self {
+            Lattice::Square => [IVec2::NEG_Y, IVec2::X, IVec2::Y, IVec2::NEG_X]
+                .map(|offset| cell + offset)
+                .to_vec(),
+            Lattice::Hexagonal => hexagonal_neighbours(cell).to_vec(),
+            Lattice::Triangular => {
+                let base = if points_up(cell) { 1 } else { -1 };
+                vec![
+                    cell + IVec2::X,
+                    cell + IVec2::new(0, base),
+                    cell + IVec2::NEG_X,
+                ]
+            }
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////
+/// UTILS
+////////////////////////////////////////////////////////////////////////
+
+// The cells touching a cell of the hexagonal lattice, clockwise from the one on the top right
+pub fn hexagonal_neighbours(cell: IVec2) -> [IVec2; 6] {
+    let shift = cell.y.rem_euclid(2);
+    [
+        IVec2::new(shift, -1),
+        IVec2::new(1, 0),
+        IVec2::new(shift, 1),
+        IVec2::new(shift - 1, 1),
+        IVec2::new(-1, 0),
+        IVec2::new(shift - 1, -1),
+    ]
+    .map(|offset| cell + offset)
+}
+// The cells touching a cell of the triangular lattice, the 5 in its row, the 5 on the side of its base then the
+// 3 on the side of its apex. Only the ones left and right of it and the one under its base share a side with it
+pub fn triangular_neighbours(cell: IVec2) -> [IVec2; 12] {
+    let (base, apex) = if points_up(cell) { (1, -1) } else { (-1, 1) };
+    [
+        IVec2::new(-2, 0),
+        IVec2::new(-1, 0),
+        IVec2::new(1, 0),
+        IVec2::new(2, 0),
+        IVec2::new(-2, base),
+        IVec2::new(-1, base),
+        IVec2::new(0, base),
+        IVec2::new(1, base),
+        IVec2::new(2, base),
+        IVec2::new(-1, apex),
+        IVec2::new(0, apex),
+        IVec2::new(1, apex),
+    ]
+    .map(|offset| cell + offset)
+}
+// Where a cell of a sheared hexagonal pattern goes, relative to its first cell which must be on an even row
+pub fn from_sheared(cell: IVec2) -> IVec2 {
+    IVec2::new(cell.x - (cell.y + 1).div_euclid(2), cell.y)
+}
+pub fn to_sheared(cell: IVec2) -> IVec2 {
+    IVec2::new(cell.x + (cell.y + 1).div_euclid(2), cell.y)
+}
+// Whether a cell of the triangular lattice has its base at the bottom
+pub fn points_up(cell: IVec2) -> bool {
+    (cell.x + cell.y).rem_euclid(2) == 0
+}
+// How far inside a triangular cell a point is, in half widths from the slanted sides or in rows from the base.
+// Negative outside
+fn triangle_depth(cell: IVec2, position: Vec2) -> f32 {
+    let from_apex = position.x - cell.x as f32 - 0.5;
+    let down = position.y - cell.y as f32;
+    let down = if points_up(cell) { down } else { 1. - down };
+    (down - from_apex.abs()).min(1. - down)
+}
+// How far right the cells of a row are, in cells
+fn row_shift(y: i32) -> f32 {
+    y.rem_euclid(2) as f32 / 2.
+}
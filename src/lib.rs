@@ -1,7 +1,37 @@
+// Section banners are written as "/// NAME" between two rules of slashes, followed by a blank line
+#![allow(clippy::empty_line_after_doc_comments)]
+#[cfg(target_arch = "wasm32")]
+mod board_material;
+#[cfg(target_arch = "wasm32")]
+mod brush;
+#[cfg(target_arch = "wasm32")]
+mod camera;
 #[cfg(target_arch = "wasm32")]
 mod game_of_life;
 #[cfg(target_arch = "wasm32")]
+mod grid;
+#[cfg(target_arch = "wasm32")]
+mod hensel;
+#[cfg(target_arch = "wasm32")]
+mod lattice;
+#[cfg(target_arch = "wasm32")]
+mod minimap;
+#[cfg(target_arch = "wasm32")]
+mod palette;
+#[cfg(target_arch = "wasm32")]
+mod rle;
+#[cfg(target_arch = "wasm32")]
+mod rule_table;
+#[cfg(target_arch = "wasm32")]
+mod rules;
+#[cfg(target_arch = "wasm32")]
+mod tiles;
+#[cfg(target_arch = "wasm32")]
+mod turmites;
+#[cfg(target_arch = "wasm32")]
 mod ui;
+#[cfg(target_arch = "wasm32")]
+mod universe;
 // mod game_of_life_ui;
 // mod game_of_life_plugin;
 #[cfg(target_arch = "wasm32")]
@@ -1,5 +1,21 @@
+// Section banners are written as "/// NAME" between two rules of slashes, followed by a blank line
+#![allow(clippy::empty_line_after_doc_comments)]
+mod board_material;
+mod brush;
+mod camera;
 mod game_of_life;
+mod grid;
+mod hensel;
+mod lattice;
+mod minimap;
+mod palette;
+mod rle;
+mod rule_table;
+mod rules;
+mod tiles;
+mod turmites;
 mod ui;
+mod universe;
 
 fn main() {
     game_of_life::init();
@@ -0,0 +1,170 @@
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+    window::PrimaryWindow,
+};
+
+use crate::camera::MainCamera;
+use crate::game_of_life::{BoardSize, GameSettings};
+use crate::universe::Universe;
+
+/**
+ *  Downsampled overview of the whole board, with the part the camera is looking at drawn on top of it.
+ *  Clicking or dragging on it moves the camera there.
+ */
+
+const MINIMAP_SIZE: f32 = 160.; // Length of the longest side of the minimap in pixels
+
+#[derive(Component)]
+pub struct Minimap;
+#[derive(Component)]
+pub struct MinimapViewport;
+
+// Spawns the minimap as a child of a UI node
+pub fn spawn(parent: &mut ChildBuilder, images: &mut Assets<Image>) {
+    let image = images.add(minimap_image(1, 1));
+    parent
+        .spawn(ImageBundle {
+            style: Style {
+                width: Val::Px(MINIMAP_SIZE),
+                height: Val::Px(MINIMAP_SIZE),
+                overflow: Overflow::clip(),
+                ..default()
+            },
+            image: UiImage::new(image),
+            ..default()
+        })
+        .insert((Minimap, Interaction::default()))
+        .with_children(|minimap| {
+            minimap
+                .spawn(NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        border: UiRect::all(Val::Px(1.)),
+                        ..default()
+                    },
+                    border_color: Color::WHITE.into(),
+                    ..default()
+                })
+                .insert(MinimapViewport);
+        });
+}
+
+////////////////////////////////////////////////////////////////////////
+/// SYSTEMS
+////////////////////////////////////////////////////////////////////////
+
+// Recomputes the density map, each pixel of the minimap averages a square of cells
+pub fn update_density(
+    mut images: ResMut<Assets<Image>>,
+    universe: Res<Universe>,
+    board_size: Res<BoardSize>,
+    settings: Res<GameSettings>,
+    mut q_minimap: Query<(&mut UiImage, &mut Style), With<Minimap>>,
+) {
+    let Ok((mut minimap_image_handle, mut style)) = q_minimap.get_single_mut() else {
+        return;
+    };
+    let longest_side = board_size.rows.max(board_size.columns);
+    let block = (longest_side as f32 / MINIMAP_SIZE).ceil().max(1.) as u32;
+    let (width, height, alive) = universe.alive_per_block(block);
+
+    let mut data = Vec::with_capacity(alive.len() * 4);
+    let cells_per_pixel = (block * block) as f32;
+    for count in alive {
+        // Any living cell should be visible even when the block is mostly empty
+        let density = (count as f32 / cells_per_pixel).sqrt();
+        for c in 0..4 {
+            let dead = settings.palette.dead[c] as f32;
+            let alive = settings.palette.alive[c] as f32;
+            data.push((dead + (alive - dead) * density).round() as u8);
+        }
+    }
+
+    let same_size = images
+        .get(&minimap_image_handle.texture)
+        .is_some_and(|image| image.size() == Vec2::new(width as f32, height as f32));
+    if same_size {
+        images.get_mut(&minimap_image_handle.texture).unwrap().data = data;
+    } else {
+        let mut image = minimap_image(width, height);
+        image.data = data;
+        images.remove(&minimap_image_handle.texture);
+        minimap_image_handle.texture = images.add(image);
+
+        // Keep the aspect ratio of the board
+        let scale = MINIMAP_SIZE / width.max(height) as f32;
+        style.width = Val::Px(width as f32 * scale);
+        style.height = Val::Px(height as f32 * scale);
+    }
+}
+
+// Draws the rectangle of the board the camera is looking at
+pub fn update_viewport(
+    q_camera: Query<(&Transform, &OrthographicProjection), With<MainCamera>>,
+    q_win: Query<&Window, With<PrimaryWindow>>,
+    board_size: Res<BoardSize>,
+    settings: Res<GameSettings>,
+    mut q_viewport: Query<&mut Style, With<MinimapViewport>>,
+) {
+    let (transform, projection) = q_camera.single();
+    let win = q_win.single();
+    let Ok(mut style) = q_viewport.get_single_mut() else {
+        return;
+    };
+
+    let board = board_size.world_size(settings.cell_size);
+    let half_view = Vec2::new(win.width(), win.height()) / 2. * projection.scale;
+    let camera = transform.translation.truncate();
+    // Top left corner of the view as a fraction of the board
+    let left = (camera.x - half_view.x + board.x / 2.) / board.x;
+    let top = (board.y / 2. - camera.y - half_view.y) / board.y;
+
+    style.left = Val::Percent(left * 100.);
+    style.top = Val::Percent(top * 100.);
+    style.width = Val::Percent(half_view.x * 2. / board.x * 100.);
+    style.height = Val::Percent(half_view.y * 2. / board.y * 100.);
+}
+
+// Moves the camera to the point of the minimap being clicked or dragged
+pub fn navigate(
+    q_minimap: Query<(&Interaction, &Node, &GlobalTransform), With<Minimap>>,
+    q_win: Query<&Window, With<PrimaryWindow>>,
+    mut q_camera: Query<&mut Transform, With<MainCamera>>,
+    board_size: Res<BoardSize>,
+    settings: Res<GameSettings>,
+) {
+    let Ok((interaction, node, transform)) = q_minimap.get_single() else {
+        return;
+    };
+    if *interaction != Interaction::Pressed {
+        return;
+    }
+    let Some(cursor) = q_win.single().cursor_position() else {
+        return;
+    };
+
+    let rect = node.logical_rect(transform);
+    let fraction = ((cursor - rect.min) / rect.size()).clamp(Vec2::ZERO, Vec2::ONE);
+    let board = board_size.world_size(settings.cell_size);
+    let mut camera = q_camera.single_mut();
+    camera.translation.x = (fraction.x - 0.5) * board.x;
+    camera.translation.y = (0.5 - fraction.y) * board.y;
+}
+
+////////////////////////////////////////////////////////////////////////
+/// UTILS
+////////////////////////////////////////////////////////////////////////
+
+fn minimap_image(width: u32, height: u32) -> Image {
+    Image::new_fill(
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0, 0, 0, 255],
+        TextureFormat::Rgba8Unorm,
+    )
+}
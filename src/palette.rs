@@ -0,0 +1,441 @@
+use bevy::prelude::*;
+
+use crate::lattice::Lattice;
+use crate::rules::Rule;
+use crate::universe::{State, Universe};
+
+/**
+ *  The colors the board is drawn with and how cells are encoded for the shader to pick their color.
+ *  Palettes can be saved as named themes to a text file next to the executable.
+ */
+
+#[cfg(not(target_arch = "wasm32"))]
+const THEMES_FILE: &str = "themes.txt";
+const ALIVE_BIT: u8 = 0x80;
+// Ages stop counting here, it's also the longest trail
+pub const MAX_LEVEL: u8 = 0x7f;
+
+// How the cells are coloured, the shader (assets/shaders/board.wgsl) relies on this order
+#[derive(PartialEq, Debug, Copy, Clone, Default)]
+pub enum ColorMode {
+    #[default]
+    Classic, // Just the alive and dead colors
+    Age,           // Living cells go from newborn to old, dead cells leave a fading trail
+    Neighbours,    // Living cells by how many living neighbours they have
+    Dying,         // Living cells that die on the next generation stand out
+    BirthSurvival, // Cells born on the last generation versus cells that survived it
+}
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct Palette {
+    pub alive: [u8; 4],
+    pub dead: [u8; 4],
+    // Age of the living cells, old cells use `alive`
+    pub newborn: [u8; 4],
+    pub young: [u8; 4],
+    pub mature: [u8; 4],
+    // Recently dead cells, fading to `dead`
+    pub trail: [u8; 4],
+    // Living cells with one and with eight living neighbours
+    pub few_neighbours: [u8; 4],
+    pub many_neighbours: [u8; 4],
+    pub dying: [u8; 4],
+    pub born: [u8; 4],
+    pub survived: [u8; 4],
+}
+
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub name: String,
+    pub palette: Palette,
+}
+
+// The themes that can be picked in the UI, the built in ones first
+#[derive(Resource, Debug, Clone)]
+pub struct Themes(pub Vec<Theme>);
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette {
+            alive: [64, 64, 243, 255],
+            dead: [0, 0, 0, 255],
+            newborn: [255, 255, 255, 255],
+            young: [255, 230, 80, 255],
+            mature: [255, 130, 40, 255],
+            trail: [32, 32, 122, 255],
+            few_neighbours: [40, 40, 140, 255],
+            many_neighbours: [160, 220, 255, 255],
+            dying: [220, 60, 60, 255],
+            born: [80, 220, 80, 255],
+            survived: [64, 64, 243, 255],
+        }
+    }
+}
+
+impl Palette {
+    // Every color with the name it's saved with
+    pub fn colors_mut(&mut self) -> [(&'static str, &mut [u8; 4]); 11] {
+        [
+            ("alive", &mut self.alive),
+            ("dead", &mut self.dead),
+            ("newborn", &mut self.newborn),
+            ("young", &mut self.young),
+            ("mature", &mut self.mature),
+            ("trail", &mut self.trail),
+            ("few_neighbours", &mut self.few_neighbours),
+            ("many_neighbours", &mut self.many_neighbours),
+            ("dying", &mut self.dying),
+            ("born", &mut self.born),
+            ("survived", &mut self.survived),
+        ]
+    }
+}
+
+impl Themes {
+    // The built in themes followed by the ones saved to the themes file
+    pub fn load() -> Self {
+        let themes = Themes(built_in_themes());
+        #[cfg(not(target_arch = "wasm32"))]
+        let themes = {
+            let mut themes = themes;
+            match std::fs::read_to_string(THEMES_FILE) {
+                Ok(text) => {
+                    for theme in parse_themes(&text) {
+                        themes.insert(theme);
+                    }
+                }
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(err) => error!("Couldn't read {}: {}", THEMES_FILE, err),
+            }
+            themes
+        };
+        themes
+    }
+    // Adds a theme, replacing the one with the same name
+    pub fn insert(&mut self, theme: Theme) {
+        match self.0.iter_mut().find(|t| t.name == theme.name) {
+            Some(existing) => *existing = theme,
+            None => self.0.push(theme),
+        }
+    }
+    // Writes every theme to the themes file, the browser build keeps them in memory
+    pub fn save(&self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Err(err) = std::fs::write(THEMES_FILE, write_themes(&self.0)) {
+            error!("Couldn't write {}: {}", THEMES_FILE, err);
+        }
+    }
+    // The name of the theme using `palette`, if any
+    pub fn name_of(&self, palette: &Palette) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|theme| theme.palette == *palette)
+            .map(|theme| theme.name.as_str())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////
+/// UTILS
+////////////////////////////////////////////////////////////////////////
+
+// The byte a cell is stored as in the board's texture. With two states the highest bit is set for living cells
+// and the rest is a level the shader colors depending on the color mode: the age, the living neighbours or a flag.
+// Rules with more states, or colors for their states, store the state itself and color it with `state_colors`
+pub fn encode_cell(universe: &Universe, rule: &Rule, x: i32, y: i32, color_mode: ColorMode) -> u8 {
+    let (Some(state), Some(age)) = (universe.get(x, y), universe.age(x, y)) else {
+        return 0;
+    };
+    if rule.colored_by_state() {
+        return state.0;
+    }
+    let alive = state == State::ALIVE;
+    let level = match (color_mode, alive) {
+        (ColorMode::Classic, _) => 0,
+        (ColorMode::Age, _) => age.min(MAX_LEVEL as u16) as u8,
+        (ColorMode::Neighbours, true) => match rule.lattice() {
+            Lattice::Square => universe.neighbours_alive(x, y),
+            // The colors go up to 8 neighbours
+            _ => universe.rule_neighbours(rule, x, y).min(8) as u8,
+        },
+        (ColorMode::Dying, true) => (universe.next_state(rule, x, y) != State::ALIVE) as u8,
+        (ColorMode::BirthSurvival, true) => (age == 0) as u8,
+        (_, false) => 0,
+    };
+    if alive {
+        ALIVE_BIT | level
+    } else {
+        level
+    }
+}
+// The color of every state of a rule colored by state, the dying states fade from `dying` to `dead`
+// unless the rule has its own colors
+pub fn state_colors(palette: &Palette, rule: &Rule) -> Vec<[u8; 4]> {
+    let states = rule.states();
+    let mut colors = vec![palette.dead, palette.alive];
+    for state in 2..states {
+        let t = (state - 1) as f32 / (states - 1) as f32;
+        let mut color = [0; 4];
+        for (c, channel) in color.iter_mut().enumerate() {
+            *channel =
+                (palette.dying[c] as f32 * (1. - t) + palette.dead[c] as f32 * t).round() as u8;
+        }
+        colors.push(color);
+    }
+    for (state, color) in rule.colors() {
+        colors[*state as usize] = *color;
+    }
+    colors
+}
+
+fn built_in_themes() -> Vec<Theme> {
+    vec![
+        Theme {
+            name: String::from("Classic"),
+            palette: Palette::default(),
+        },
+        // https://jfly.uni-koeln.de/color/ colors, distinguishable with every kind of colour blindness
+        Theme {
+            name: String::from("Okabe-Ito (colour-blind safe)"),
+            palette: Palette {
+                alive: [0, 114, 178, 255],
+                dead: [0, 0, 0, 255],
+                newborn: [240, 228, 66, 255],
+                young: [230, 159, 0, 255],
+                mature: [86, 180, 233, 255],
+                trail: [0, 57, 89, 255],
+                few_neighbours: [0, 114, 178, 255],
+                many_neighbours: [240, 228, 66, 255],
+                dying: [213, 94, 0, 255],
+                born: [230, 159, 0, 255],
+                survived: [86, 180, 233, 255],
+            },
+        },
+        // Only lightness changes, safe for any colour vision
+        Theme {
+            name: String::from("Grayscale (colour-blind safe)"),
+            palette: Palette {
+                alive: [200, 200, 200, 255],
+                dead: [0, 0, 0, 255],
+                newborn: [255, 255, 255, 255],
+                young: [235, 235, 235, 255],
+                mature: [215, 215, 215, 255],
+                trail: [70, 70, 70, 255],
+                few_neighbours: [90, 90, 90, 255],
+                many_neighbours: [255, 255, 255, 255],
+                dying: [100, 100, 100, 255],
+                born: [255, 255, 255, 255],
+                survived: [150, 150, 150, 255],
+            },
+        },
+        Theme {
+            name: String::from("Viridis"),
+            palette: Palette {
+                alive: [33, 145, 140, 255],
+                dead: [68, 1, 84, 255],
+                newborn: [253, 231, 37, 255],
+                young: [94, 201, 98, 255],
+                mature: [53, 183, 121, 255],
+                trail: [59, 82, 139, 255],
+                few_neighbours: [59, 82, 139, 255],
+                many_neighbours: [253, 231, 37, 255],
+                dying: [253, 231, 37, 255],
+                born: [94, 201, 98, 255],
+                survived: [49, 104, 142, 255],
+            },
+        },
+        Theme {
+            name: String::from("Paper"),
+            palette: Palette {
+                alive: [30, 30, 30, 255],
+                dead: [245, 240, 225, 255],
+                newborn: [200, 40, 40, 255],
+                young: [150, 60, 40, 255],
+                mature: [80, 50, 40, 255],
+                trail: [215, 200, 170, 255],
+                few_neighbours: [120, 120, 120, 255],
+                many_neighbours: [0, 0, 0, 255],
+                dying: [200, 40, 40, 255],
+                born: [40, 120, 200, 255],
+                survived: [30, 30, 30, 255],
+            },
+        },
+    ]
+}
+
+// Themes are saved as
+// [Name]
+// alive = 4040f3ff
+// ...
+#[cfg(not(target_arch = "wasm32"))]
+fn write_themes(themes: &[Theme]) -> String {
+    let mut text = String::new();
+    for theme in themes {
+        text.push_str(&format!("[{}]\n", theme.name));
+        let mut palette = theme.palette;
+        for (name, color) in palette.colors_mut() {
+            text.push_str(&format!(
+                "{} = {:02x}{:02x}{:02x}{:02x}\n",
+                name, color[0], color[1], color[2], color[3]
+            ));
+        }
+        text.push('\n');
+    }
+    text
+}
+// Reads the themes written by `write_themes`, colors that are missing or can't be read keep the classic ones
+#[cfg(not(target_arch = "wasm32"))]
+fn parse_themes(text: &str) -> Vec<Theme> {
+    let mut themes: Vec<Theme> = vec![];
+    for line in text.lines().map(str::trim) {
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            themes.push(Theme {
+                name: name.to_string(),
+                palette: Palette::default(),
+            });
+            continue;
+        }
+        let (Some(theme), Some((key, value))) = (themes.last_mut(), line.split_once('=')) else {
+            continue;
+        };
+        let Some(color) = parse_hex_color(value.trim()) else {
+            warn!("Invalid color in theme {}: {}", theme.name, line);
+            continue;
+        };
+        if let Some((_, c)) = theme
+            .palette
+            .colors_mut()
+            .into_iter()
+            .find(|(name, _)| *name == key.trim())
+        {
+            *c = color;
+        }
+    }
+    themes
+}
+#[cfg(not(target_arch = "wasm32"))]
+fn parse_hex_color(hex: &str) -> Option<[u8; 4]> {
+    if hex.len() != 8 {
+        return None;
+    }
+    let mut color = [0; 4];
+    for (c, channel) in color.iter_mut().enumerate() {
+        *channel = u8::from_str_radix(hex.get(c * 2..c * 2 + 2)?, 16).ok()?;
+    }
+    Some(color)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A universe with the living cells given
+    fn universe(width: u32, height: u32, alive: &[(i32, i32)]) -> Universe {
+        let mut universe = Universe::new(width, height);
+        for (x, y) in alive {
+            universe.set(*x, *y, State::ALIVE);
+        }
+        universe
+    }
+    const BLOCK: [(i32, i32); 4] = [(1, 1), (2, 1), (1, 2), (2, 2)];
+    const BLINKER: [(i32, i32); 3] = [(1, 2), (2, 2), (3, 2)];
+
+    #[test]
+    fn classic_only_has_the_alive_bit() {
+        let rule = Rule::default();
+        let universe = universe(4, 4, &BLOCK);
+        assert_eq!(
+            encode_cell(&universe, &rule, 1, 1, ColorMode::Classic),
+            ALIVE_BIT
+        );
+        assert_eq!(encode_cell(&universe, &rule, 0, 0, ColorMode::Classic), 0);
+        // Outside the board
+        assert_eq!(encode_cell(&universe, &rule, -1, 9, ColorMode::Classic), 0);
+    }
+
+    #[test]
+    fn age_saturates_at_the_max_level() {
+        let rule = Rule::default();
+        let mut universe = universe(4, 4, &BLOCK);
+        assert_eq!(
+            encode_cell(&universe, &rule, 1, 1, ColorMode::Age),
+            ALIVE_BIT
+        );
+        universe.step(&rule);
+        assert_eq!(
+            encode_cell(&universe, &rule, 1, 1, ColorMode::Age),
+            ALIVE_BIT | 1
+        );
+        for _ in 0..MAX_LEVEL as u32 + 10 {
+            universe.step(&rule);
+        }
+        assert_eq!(
+            encode_cell(&universe, &rule, 1, 1, ColorMode::Age),
+            ALIVE_BIT | MAX_LEVEL
+        );
+        // Cells that were never alive are at the end of their trail
+        assert_eq!(
+            encode_cell(&universe, &rule, 0, 0, ColorMode::Age),
+            MAX_LEVEL
+        );
+    }
+
+    #[test]
+    fn neighbours_are_counted_for_living_cells() {
+        let rule = Rule::default();
+        let universe = universe(5, 5, &BLINKER);
+        assert_eq!(
+            encode_cell(&universe, &rule, 2, 2, ColorMode::Neighbours),
+            ALIVE_BIT | 2
+        );
+        assert_eq!(
+            encode_cell(&universe, &rule, 1, 2, ColorMode::Neighbours),
+            ALIVE_BIT | 1
+        );
+        assert_eq!(
+            encode_cell(&universe, &rule, 2, 1, ColorMode::Neighbours),
+            0
+        );
+    }
+
+    #[test]
+    fn dying_flags_cells_that_die_next() {
+        let rule = Rule::default();
+        let universe = universe(5, 5, &BLINKER);
+        assert_eq!(
+            encode_cell(&universe, &rule, 2, 2, ColorMode::Dying),
+            ALIVE_BIT
+        );
+        assert_eq!(
+            encode_cell(&universe, &rule, 1, 2, ColorMode::Dying),
+            ALIVE_BIT | 1
+        );
+        assert_eq!(encode_cell(&universe, &rule, 2, 1, ColorMode::Dying), 0);
+    }
+
+    #[test]
+    fn birth_survival_flags_newborn_cells() {
+        let rule = Rule::default();
+        let mut universe = universe(5, 5, &BLINKER);
+        universe.step(&rule);
+        let encode = |x, y| encode_cell(&universe, &rule, x, y, ColorMode::BirthSurvival);
+        assert_eq!(encode(2, 1), ALIVE_BIT | 1);
+        assert_eq!(encode(2, 2), ALIVE_BIT);
+        assert_eq!(encode(1, 2), 0);
+    }
+
+    #[test]
+    fn generations_store_the_state() {
+        // Brian's Brain, living cells go through one dying state
+        let rule = Rule::parse("B2/S/C3").unwrap();
+        let mut universe = universe(5, 5, &[(1, 2), (3, 2)]);
+        for color_mode in [ColorMode::Classic, ColorMode::Age, ColorMode::Neighbours] {
+            assert_eq!(encode_cell(&universe, &rule, 1, 2, color_mode), 1);
+        }
+        universe.step(&rule);
+        assert_eq!(universe.get(1, 2), Some(State(2)));
+        assert_eq!(encode_cell(&universe, &rule, 1, 2, ColorMode::Age), 2);
+        assert_eq!(encode_cell(&universe, &rule, 2, 1, ColorMode::Age), 1);
+        universe.step(&rule);
+        assert_eq!(encode_cell(&universe, &rule, 1, 2, ColorMode::Age), 0);
+    }
+}
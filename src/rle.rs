@@ -0,0 +1,349 @@
+use bevy::prelude::*;
+
+use crate::lattice::{self, Lattice};
+use crate::rules::Rule;
+use crate::universe::{State, Universe, MAX_CELLS, MAX_SIDE};
+
+/**
+ *  Patterns in the run length encoded format most Life programs share (https://conwaylife.com/wiki/Run_Length_Encoded).
+ *  Two state patterns use `b` for dead and `o` for alive cells. Patterns with more states use `.` for dead,
+ *  `A` to `X` for states 1 to 24 and a prefix from `p` to `y` for the next ones (`pA` is 25, `qA` 49...).
+ */
+
+const LINE_LENGTH: usize = 70; // Lines of written patterns don't get longer than this
+const STATES_PER_PREFIX: u8 = 24;
+
+// Patterns that can be picked in the UI, their header has the rule they run with
+pub const LIBRARY: [(&str, &str); 3] = [
+    (
+        "WireWorld diodes",
+        concat!(
+            "#C Electrons from the clocks go through the top diode, the bottom one stops them\n",
+            "x = 16, y = 9, rule = WireWorld\n",
+            ".AB$C2.C6.2C$.2C.7C.4C$10.2C2$.AB$C2.C6.2C$.2C.6C.5C$10.2C!",
+        ),
+    ),
+    (
+        "WireWorld clock generators",
+        concat!(
+            "#C Loops sending an electron down their wire every 6 and 14 generations\n",
+            "x = 13, y = 9, rule = WireWorld\n",
+            ".AB$C2.C$.2C.9C3$.AB3C$C5.C$C5.C$.5C.6C!",
+        ),
+    ),
+    (
+        "WireWorld half adder",
+        concat!(
+            "#C Adds the electrons on the two wires on the left, remove either of them to\n",
+            "#C try the other inputs. The sum leaves on the bottom right, the carry above it.\n",
+            "#C The sum is A xor B, the carry (A or sum) xor sum\n",
+            "x = 67, y = 24, rule = WireWorld\n",
+            "34.2C$5.30C.9C$4.C29.2C9.C$3.C42.10C$3.C30.2C9.C10.C$3.C20.\n",
+            "11C.9C10.4C$3.C20.C9.2C19.C2.9C$3.C20.C30.4C$3.C20.C31.C$3.C\n",
+            "20.C5.4C3.19C$3.C20.C5.C2.C3.C$3.C20.C5.C2.C3.C$3.C20.C5.C2.\n",
+            "C3.C$3.C20.C5.C2.5C$3.C20.C5.C$3.C20.C5.C$3.C20.C5.C$BA12C\n",
+            "10.C5.C$14.C9.C5.C$13.4C7.C5.C$13.C2.51C$13.4C$14.C$BA12C!",
+        ),
+    ),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    pub width: u32,
+    pub height: u32,
+    // Row by row
+    pub cells: Vec<State>,
+    // The rule in the header, if any
+    pub rule: Option<String>,
+}
+
+impl Pattern {
+    // The smallest rectangle holding every cell of the universe that isn't dead, None if they all are.
+    // Hexagonal cells are sheared the way Golly writes them (see `lattice::to_sheared`), triangular patterns
+    // start with a cell pointing up. Cells spread over more than `MAX_CELLS` cells don't make a pattern
+    pub fn from_universe(universe: &Universe, lattice: Lattice) -> Result<Option<Pattern>, String> {
+        let mut placed: Vec<_> = universe.occupied().collect();
+        let Some(top) = placed.iter().map(|(cell, _)| cell.y).min() else {
+            return Ok(None);
+        };
+        if lattice == Lattice::Hexagonal {
+            // Sheared from an even row so the rows keep their parity
+            let first = top & !1;
+            for (cell, _) in placed.iter_mut() {
+                *cell = lattice::to_sheared(*cell - IVec2::new(0, first));
+            }
+        }
+        let (first, _) = placed[0];
+        let (mut min, max) = placed.iter().fold((first, first), |(min, max), (cell, _)| {
+            (min.min(*cell), max.max(*cell))
+        });
+        if lattice == Lattice::Triangular && !lattice::points_up(min) {
+            // The first cell of triangular patterns points up
+            min.x -= 1;
+        }
+        let size = (max - min + 1).as_uvec2();
+        if size.x as usize * size.y as usize > MAX_CELLS {
+            return Err(format!(
+                "The cells are too far apart, patterns have at most {} cells",
+                MAX_CELLS
+            ));
+        }
+        let mut cells = vec![State::DEAD; (size.x * size.y) as usize];
+        for (cell, state) in placed {
+            let cell = (cell - min).as_uvec2();
+            cells[(cell.y * size.x + cell.x) as usize] = state;
+        }
+        Ok(Some(Pattern {
+            width: size.x,
+            height: size.y,
+            cells,
+            rule: None,
+        }))
+    }
+    pub fn get(&self, x: u32, y: u32) -> State {
+        self.cells[(y * self.width + x) as usize]
+    }
+}
+
+////////////////////////////////////////////////////////////////////////
+/// UTILS
+////////////////////////////////////////////////////////////////////////
+
+// Reads a pattern, the size in the header is only a minimum since some programs get it wrong. Patterns are
+// stored whole, those with more than `MAX_CELLS` cells or that wouldn't fit on the biggest board are refused
+pub fn parse(text: &str) -> Result<Pattern, String> {
+    let (mut width, mut height, mut rule) = (0usize, 0usize, None);
+    let mut body = String::new();
+    for line in text.lines().map(str::trim) {
+        if line.starts_with('#') || line.is_empty() {
+            continue;
+        }
+        if body.is_empty() && line.starts_with('x') {
+            for field in line.split(',') {
+                let Some((key, value)) = field.split_once('=') else {
+                    return Err(format!("Invalid header field \"{}\"", field.trim()));
+                };
+                let value = value.trim();
+                match key.trim() {
+                    "x" => width = value.parse().map_err(|_| "Invalid width in the header")?,
+                    "y" => height = value.parse().map_err(|_| "Invalid height in the header")?,
+                    "rule" => rule = Some(value.to_string()),
+                    _ => {}
+                }
+            }
+            continue;
+        }
+        body.push_str(line);
+    }
+    let too_big = || {
+        format!(
+            "The pattern is too big, patterns have at most {} cells with at most {} on a side",
+            MAX_CELLS, MAX_SIDE
+        )
+    };
+    let fits = |width: usize, height: usize| {
+        u32::try_from(width)
+            .ok()
+            .zip(u32::try_from(height).ok())
+            .is_some_and(|(width, height)| Universe::fits(width, height))
+            && width
+                .checked_mul(height)
+                .is_some_and(|len| len <= MAX_CELLS)
+    };
+    if !fits(width, height) {
+        return Err(too_big());
+    }
+
+    // Runs of cells that aren't dead as their first cell, their length and their state
+    let mut runs = vec![];
+    let (mut x, mut y) = (0usize, 0usize);
+    let mut count: Option<usize> = None;
+    let mut prefix = None;
+    for c in body.chars() {
+        let state = match (prefix, c) {
+            (None, '0'..='9') => {
+                let digit = c as usize - '0' as usize;
+                let more = count.unwrap_or(0).checked_mul(10);
+                count = Some(
+                    more.and_then(|n| n.checked_add(digit))
+                        .ok_or_else(too_big)?,
+                );
+                continue;
+            }
+            (None, 'p'..='y') => {
+                prefix = Some(c as u8 - b'p' + 1);
+                continue;
+            }
+            (Some(p), 'A'..='X') => {
+                let state = p as u16 * STATES_PER_PREFIX as u16 + (c as u8 - b'A') as u16 + 1;
+                prefix = None;
+                u8::try_from(state).map_err(|_| format!("State {} is too high", state))?
+            }
+            (Some(_), _) => return Err(format!("Expected a state after the prefix, got '{}'", c)),
+            (None, 'b' | '.') => 0,
+            (None, 'o') => 1,
+            (None, 'A'..='X') => c as u8 - b'A' + 1,
+            (None, '$') => {
+                y = y
+                    .checked_add(count.take().unwrap_or(1))
+                    .ok_or_else(too_big)?;
+                x = 0;
+                continue;
+            }
+            (None, '!') => break,
+            _ => return Err(format!("Unexpected '{}' in the pattern", c)),
+        };
+        let run = count.take().unwrap_or(1);
+        let end = x.checked_add(run).ok_or_else(too_big)?;
+        if !fits(width.max(end), height.max(y.saturating_add(1))) {
+            return Err(too_big());
+        }
+        if state != 0 {
+            runs.push((x, y, run, State(state)));
+        }
+        x = end;
+        width = width.max(x);
+        height = height.max(y + 1);
+    }
+
+    let mut cells = vec![State::DEAD; width * height];
+    for (x, y, run, state) in runs {
+        let first = y * width + x;
+        cells[first..first + run].fill(state);
+    }
+    Ok(Pattern {
+        width: width as u32,
+        height: height as u32,
+        cells,
+        rule,
+    })
+}
+
+// Writes a pattern for `rule`, with `b` and `o` when it only has two states
+pub fn write(pattern: &Pattern, rule: &Rule) -> String {
+    let two_states = rule.states() == 2;
+    let token = |state: State| -> String {
+        match state {
+            State::DEAD if two_states => String::from("b"),
+            State::ALIVE if two_states => String::from("o"),
+            State::DEAD => String::from("."),
+            State(n) => {
+                let prefix = (n - 1) / STATES_PER_PREFIX;
+                let letter = ((n - 1) % STATES_PER_PREFIX + b'A') as char;
+                match prefix {
+                    0 => letter.to_string(),
+                    _ => format!("{}{}", (b'p' + prefix - 1) as char, letter),
+                }
+            }
+        }
+    };
+
+    // Runs of the same token, the dead cells at the end of a row and the empty rows at the end are left out
+    let mut runs: Vec<(u32, String)> = vec![];
+    let push = |runs: &mut Vec<(u32, String)>, count: u32, token: String| match runs.last_mut() {
+        Some((last_count, last)) if *last == token => *last_count += count,
+        _ => runs.push((count, token)),
+    };
+    for y in 0..pattern.height {
+        let row: Vec<State> = (0..pattern.width).map(|x| pattern.get(x, y)).collect();
+        let end = row
+            .iter()
+            .rposition(|s| *s != State::DEAD)
+            .map_or(0, |i| i + 1);
+        for state in &row[..end] {
+            push(&mut runs, 1, token(*state));
+        }
+        if y + 1 < pattern.height {
+            push(&mut runs, 1, String::from("$"));
+        }
+    }
+    while runs.last().is_some_and(|(_, token)| token == "$") {
+        runs.pop();
+    }
+
+    let mut text = format!(
+        "x = {}, y = {}, rule = {}\n",
+        pattern.width, pattern.height, rule
+    );
+    let mut line = String::new();
+    for (count, token) in runs {
+        let run = match count {
+            1 => token,
+            _ => format!("{}{}", count, token),
+        };
+        if line.len() + run.len() > LINE_LENGTH {
+            text.push_str(&line);
+            text.push('\n');
+            line.clear();
+        }
+        line.push_str(&run);
+    }
+    line.push('!');
+    text.push_str(&line);
+    text.push('\n');
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_a_glider() {
+        let pattern = parse("#N Glider\nx = 3, y = 3, rule = B3/S23\nbob$2bo$3o!").unwrap();
+        assert_eq!((pattern.width, pattern.height), (3, 3));
+        assert_eq!(pattern.rule.as_deref(), Some("B3/S23"));
+        let alive: Vec<bool> = pattern.cells.iter().map(|c| *c == State::ALIVE).collect();
+        let o = true;
+        let b = false;
+        assert_eq!(alive, [b, o, b, b, b, o, o, o, o]);
+    }
+
+    #[test]
+    fn reads_states_with_a_prefix() {
+        let pattern = parse("x = 3, y = 1, rule = B2/S/C40\n.ApA!").unwrap();
+        assert_eq!(pattern.cells, [State::DEAD, State(1), State(25)]);
+        assert!(parse("yX!").is_err());
+    }
+
+    #[test]
+    fn writes_what_it_reads() {
+        let rule = Rule::default();
+        let pattern = parse("x = 3, y = 3\nbob$2bo$3o!").unwrap();
+        let written = write(&pattern, &rule);
+        assert_eq!(written, "x = 3, y = 3, rule = B3/S23\nbo$2bo$3o!\n");
+        assert_eq!(parse(&written).unwrap().cells, pattern.cells);
+    }
+
+    #[test]
+    fn refuses_patterns_bigger_than_the_board() {
+        for text in [
+            "4294967295o!",
+            "99999999999999999999999999o!",
+            "o4294967295$o!",
+            "18446744073709551615$o!",
+            "x = 4294967295, y = 4294967295\no!",
+            // Each row fits, the rectangle around them doesn't
+            "100000o99999$o!",
+        ] {
+            assert!(parse(text).is_err(), "{}", text);
+        }
+        assert!(parse(&format!("{}o!", MAX_SIDE)).is_ok());
+    }
+
+    #[test]
+    fn exports_the_cells_of_big_boards() {
+        let mut universe = Universe::new(MAX_SIDE, MAX_SIDE);
+        assert_eq!(Pattern::from_universe(&universe, Lattice::Square), Ok(None));
+        universe.set(60_000, 70_000, State::ALIVE);
+        universe.set(60_002, 70_001, State(2));
+        let pattern = Pattern::from_universe(&universe, Lattice::Square)
+            .unwrap()
+            .unwrap();
+        assert_eq!((pattern.width, pattern.height), (3, 2));
+        assert_eq!(pattern.get(2, 1), State(2));
+        // A pattern holding both corners would be too big
+        universe.set(0, 0, State::ALIVE);
+        assert!(Pattern::from_universe(&universe, Lattice::Square).is_err());
+    }
+}
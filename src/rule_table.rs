@@ -0,0 +1,607 @@
+use std::collections::HashMap;
+
+use crate::rules::Neighbourhood;
+
+/**
+ *  Rules written in Golly's .rule format (https://golly.sourceforge.io/Help/formats.html#rule), so any automaton
+ *  can be loaded without code: @TABLE lists transitions with variables and symmetries, @TREE is a decision tree
+ *  over the states around the cell and @COLORS gives the states their colors.
+ *  The states around a cell are passed in the order C, N, NE, E, SE, S, SW, W, NW (C, N, E, S, W for von Neumann,
+ *  C, N, E, SE, S, W, NW for hexagonal tables), the cells outside the board are in state 0.
+ */
+
+// Inputs of a transition, the cell and its neighbours
+pub const MAX_INPUTS: usize = 9;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleTable {
+    pub name: String,
+    pub states: u16,
+    pub neighbourhood: Neighbourhood,
+    lookup: Lookup,
+    // Colors given by the file, the other states use the palette
+    pub colors: Vec<(u8, [u8; 4])>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Lookup {
+    // The first transition that matches gives the next state, cells that match none stay the same
+    Table {
+        transitions: Vec<Transition>,
+        // The neighbours can match the transition in any order
+        permute: bool,
+    },
+    // Each node has a child per state, the nodes of level 1 hold the next states. The root is the last node
+    Tree {
+        nodes: Vec<Vec<u32>>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Transition {
+    // The states each input can be in, the cell first
+    inputs: Vec<StateSet>,
+    output: u8,
+}
+
+// A set of states, one bit per state
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+struct StateSet([u64; 4]);
+
+impl StateSet {
+    fn insert(&mut self, state: u8) {
+        self.0[state as usize / 64] |= 1 << (state % 64);
+    }
+    fn contains(&self, state: u8) -> bool {
+        self.0[state as usize / 64] & (1 << (state % 64)) != 0
+    }
+}
+
+impl RuleTable {
+    // The next state of a cell given the states around it, see the order above
+    pub fn next(&self, around: &[u8; MAX_INPUTS]) -> u8 {
+        let inputs = &around[..self.inputs()];
+        match &self.lookup {
+            Lookup::Table {
+                transitions,
+                permute,
+            } => transitions
+                .iter()
+                .find(|transition| {
+                    let cell = transition.inputs[0].contains(inputs[0]);
+                    if *permute {
+                        cell && permutation_matches(&transition.inputs[1..], &inputs[1..])
+                    } else {
+                        cell && transition
+                            .inputs
+                            .iter()
+                            .zip(inputs)
+                            .all(|(set, state)| set.contains(*state))
+                    }
+                })
+                .map_or(inputs[0], |transition| transition.output),
+            Lookup::Tree { nodes } => {
+                // Trees read the neighbours in the order NW, NE, SW, SE, N, W, E, S then the cell
+                const MOORE: [usize; 9] = [8, 2, 6, 4, 1, 7, 3, 5, 0];
+                const VON_NEUMANN: [usize; 5] = [1, 4, 2, 3, 0];
+                let order: &[usize] = match self.neighbourhood {
+                    Neighbourhood::VonNeumann => &VON_NEUMANN,
+                    _ => &MOORE,
+                };
+                let mut node = nodes.len() - 1;
+                for &input in &order[..order.len() - 1] {
+                    node = nodes[node][inputs[input] as usize] as usize;
+                }
+                nodes[node][inputs[0] as usize] as u8
+            }
+        }
+    }
+    // The cell and its neighbours
+    fn inputs(&self) -> usize {
+        self.neighbourhood.size() + 1
+    }
+}
+
+////////////////////////////////////////////////////////////////////////
+/// UTILS
+////////////////////////////////////////////////////////////////////////
+
+// Reads a .rule file, only the @TABLE or @TREE and the @COLORS sections are used
+pub fn parse(text: &str) -> Result<RuleTable, String> {
+    let mut name = String::new();
+    let mut section = "";
+    let mut table = vec![];
+    let mut tree = vec![];
+    let mut colors = vec![];
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('@') {
+            let mut words = header.split_whitespace();
+            section = words.next().unwrap_or_default();
+            if section == "RULE" {
+                name = words.collect::<Vec<_>>().join(" ");
+            }
+            continue;
+        }
+        match section {
+            "TABLE" => table.push(line),
+            "TREE" => tree.push(line),
+            "COLORS" => colors.push(line),
+            _ => {}
+        }
+    }
+    if name.is_empty() {
+        return Err(String::from("The file has no @RULE name"));
+    }
+    let mut rule = if !table.is_empty() {
+        parse_table(&table)?
+    } else if !tree.is_empty() {
+        parse_tree(&tree)?
+    } else {
+        return Err(String::from("The file has no @TABLE or @TREE"));
+    };
+    rule.name = name;
+    for line in colors {
+        rule.colors.extend(parse_colors(line, rule.states)?);
+    }
+    rule.colors
+        .retain(|(state, _)| (*state as u16) < rule.states);
+    Ok(rule)
+}
+
+fn parse_table(lines: &[&str]) -> Result<RuleTable, String> {
+    let mut states = None;
+    let mut neighbourhood = None;
+    let mut symmetries = "none";
+    let mut variables: HashMap<&str, Vec<u8>> = HashMap::new();
+    let mut transitions = vec![];
+    for &line in lines {
+        if let Some((key, value)) = line.split_once(':') {
+            let value = value.trim();
+            match key.trim() {
+                "n_states" => states = Some(parse_states(value)?),
+                "neighborhood" => {
+                    neighbourhood = Some(match value {
+                        "Moore" => Neighbourhood::Moore,
+                        "vonNeumann" => Neighbourhood::VonNeumann,
+                        "hexagonal" => Neighbourhood::Hexagonal,
+                        _ => return Err(format!("The {} neighbourhood isn't supported", value)),
+                    })
+                }
+                "symmetries" => symmetries = value,
+                _ => return Err(format!("Unknown setting \"{}\"", key.trim())),
+            }
+            continue;
+        }
+        let states = states.ok_or("n_states must come before the transitions")?;
+        if let Some(variable) = line.strip_prefix("var ") {
+            let (name, values) = variable
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid variable \"{}\"", line))?;
+            let values = values
+                .trim()
+                .strip_prefix('{')
+                .and_then(|v| v.strip_suffix('}'))
+                .ok_or_else(|| format!("Invalid variable \"{}\"", line))?;
+            let mut set = vec![];
+            for value in values.split(',').map(str::trim) {
+                set.extend(parse_value(value, states, &variables)?);
+            }
+            variables.insert(name.trim(), set);
+            continue;
+        }
+        let neighbourhood = neighbourhood.ok_or("neighborhood must come before the transitions")?;
+        // Transitions are separated by commas, or written as one digit per state when there are less than 11
+        let tokens: Vec<&str> = if line.contains(',') {
+            line.split(',').map(str::trim).collect()
+        } else {
+            line.split_whitespace()
+                .flat_map(|word| (0..word.len()).map(move |i| &word[i..i + 1]))
+                .collect()
+        };
+        let inputs = neighbourhood.size() + 1;
+        if tokens.len() != inputs + 1 {
+            return Err(format!("Expected {} states in \"{}\"", inputs + 1, line));
+        }
+        transitions.extend(expand_transition(&tokens, states, &variables)?);
+    }
+    let states = states.ok_or("The table has no n_states")?;
+    let neighbourhood = neighbourhood.ok_or("The table has no neighborhood")?;
+
+    // Every symmetric copy of the transitions, as permutations of the neighbours around the cell
+    let neighbours = neighbourhood.size();
+    let rotation = |step: usize| (0..neighbours).map(move |i| (i + step) % neighbours);
+    // Mirrored left to right, the ring of neighbours starts at N
+    let reflection = |order: Vec<usize>| -> Vec<usize> {
+        (0..neighbours)
+            .map(|i| order[(neighbours - i) % neighbours])
+            .collect()
+    };
+    // The symmetries Golly knows for each neighbourhood, rotateN turns the ring N times
+    let (turns, reflect) = match (neighbourhood, symmetries) {
+        (_, "none" | "permute") => (1, false),
+        (Neighbourhood::Moore | Neighbourhood::VonNeumann, "reflect_horizontal") => (1, true),
+        (Neighbourhood::Moore | Neighbourhood::VonNeumann, "rotate4") => (4, false),
+        (Neighbourhood::Moore | Neighbourhood::VonNeumann, "rotate4reflect") => (4, true),
+        (Neighbourhood::Moore, "rotate8") => (8, false),
+        (Neighbourhood::Moore, "rotate8reflect") => (8, true),
+        (Neighbourhood::Hexagonal, "rotate2") => (2, false),
+        (Neighbourhood::Hexagonal, "rotate3") => (3, false),
+        (Neighbourhood::Hexagonal, "rotate6") => (6, false),
+        (Neighbourhood::Hexagonal, "rotate6reflect") => (6, true),
+        _ => {
+            return Err(format!(
+                "The {} symmetries aren't supported with this neighbourhood",
+                symmetries
+            ))
+        }
+    };
+    let rotations: Vec<Vec<usize>> = (0..turns)
+        .map(|r| rotation(r * neighbours / turns).collect())
+        .collect();
+    let mut orders = rotations.clone();
+    if reflect {
+        orders.extend(rotations.into_iter().map(reflection));
+    }
+    let transitions = transitions
+        .iter()
+        .flat_map(|transition: &Transition| {
+            orders.iter().map(|order| {
+                let mut inputs = vec![transition.inputs[0]];
+                inputs.extend(order.iter().map(|i| transition.inputs[1 + i]));
+                Transition {
+                    inputs,
+                    output: transition.output,
+                }
+            })
+        })
+        .collect();
+    Ok(RuleTable {
+        name: String::new(),
+        states,
+        neighbourhood,
+        lookup: Lookup::Table {
+            transitions,
+            permute: symmetries == "permute",
+        },
+        colors: vec![],
+    })
+}
+// One transition for each value of the variables that appear more than once, those have the same value everywhere
+fn expand_transition(
+    tokens: &[&str],
+    states: u16,
+    variables: &HashMap<&str, Vec<u8>>,
+) -> Result<Vec<Transition>, String> {
+    let (output, inputs) = tokens.split_last().unwrap();
+    let mut bound: Vec<&str> = vec![];
+    for token in tokens {
+        let repeated = tokens.iter().filter(|t| *t == token).count() > 1;
+        if variables.contains_key(token) && repeated && !bound.contains(token) {
+            bound.push(token);
+        }
+    }
+    if variables.contains_key(output) && !bound.contains(output) {
+        return Err(format!("The output {} isn't one of the inputs", output));
+    }
+
+    let mut transitions = vec![];
+    let mut values = vec![0; bound.len()];
+    loop {
+        let value_of = |token: &str| -> Result<Vec<u8>, String> {
+            match bound.iter().position(|b| *b == token) {
+                Some(i) => Ok(vec![variables[token][values[i]]]),
+                None => parse_value(token, states, variables),
+            }
+        };
+        let mut sets = vec![];
+        for token in inputs {
+            let mut set = StateSet::default();
+            for state in value_of(token)? {
+                set.insert(state);
+            }
+            sets.push(set);
+        }
+        transitions.push(Transition {
+            inputs: sets,
+            output: value_of(output)?[0],
+        });
+        // Next combination of the bound variables
+        let Some(i) = (0..bound.len()).find(|&i| values[i] + 1 < variables[bound[i]].len()) else {
+            break;
+        };
+        values[i] += 1;
+        values[..i].fill(0);
+    }
+    Ok(transitions)
+}
+// A state or the states of a variable
+fn parse_value(
+    value: &str,
+    states: u16,
+    variables: &HashMap<&str, Vec<u8>>,
+) -> Result<Vec<u8>, String> {
+    if let Some(set) = variables.get(value) {
+        return Ok(set.clone());
+    }
+    match value.parse::<u16>() {
+        Ok(state) if state < states => Ok(vec![state as u8]),
+        _ => Err(format!("\"{}\" isn't a state or a variable", value)),
+    }
+}
+
+fn parse_tree(lines: &[&str]) -> Result<RuleTable, String> {
+    let (mut states, mut neighbours) = (None, None);
+    let mut nodes: Vec<Vec<u32>> = vec![];
+    let mut levels = vec![];
+    for &line in lines {
+        if let Some((key, value)) = line.split_once('=') {
+            let value = value.trim();
+            match key.trim() {
+                "num_states" => states = Some(parse_states(value)?),
+                "num_neighbors" => {
+                    neighbours = Some(match value {
+                        "8" => Neighbourhood::Moore,
+                        "4" => Neighbourhood::VonNeumann,
+                        _ => {
+                            return Err(format!("Trees with {} neighbours aren't supported", value))
+                        }
+                    })
+                }
+                "num_nodes" => {}
+                _ => return Err(format!("Unknown setting \"{}\"", key.trim())),
+            }
+            continue;
+        }
+        let states = states.ok_or("num_states must come before the nodes")?;
+        let numbers = line
+            .split_whitespace()
+            .map(str::parse::<u32>)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| format!("Invalid node \"{}\"", line))?;
+        let Some((level, children)) = numbers.split_first() else {
+            continue;
+        };
+        // Level 1 nodes hold states, the others earlier nodes
+        let valid = children.len() == states as usize
+            && children.iter().all(|&child| match level {
+                1 => child < states as u32,
+                _ => (child as usize) < nodes.len() && levels[child as usize] == level - 1,
+            });
+        if !valid {
+            return Err(format!("Invalid node \"{}\"", line));
+        }
+        levels.push(*level);
+        nodes.push(children.to_vec());
+    }
+    let states = states.ok_or("The tree has no num_states")?;
+    let neighbourhood = neighbours.ok_or("The tree has no num_neighbors")?;
+    let inputs = if neighbourhood == Neighbourhood::VonNeumann {
+        5
+    } else {
+        9
+    };
+    if levels.last() != Some(&inputs) {
+        return Err(format!(
+            "The last node must be the root, of level {}",
+            inputs
+        ));
+    }
+    Ok(RuleTable {
+        name: String::new(),
+        states,
+        neighbourhood,
+        lookup: Lookup::Tree { nodes },
+        colors: vec![],
+    })
+}
+fn parse_states(value: &str) -> Result<u16, String> {
+    match value.parse::<u16>() {
+        Ok(states) if (2..=256).contains(&states) => Ok(states),
+        _ => Err(String::from("The number of states goes from 2 to 256")),
+    }
+}
+// "state red green blue" from 0 to 255, or a gradient "red green blue red green blue" over the states 1 to N-1
+fn parse_colors(line: &str, states: u16) -> Result<Vec<(u8, [u8; 4])>, String> {
+    let numbers = line
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|word| !word.is_empty())
+        .map(str::parse::<u8>)
+        .collect::<Result<Vec<_>, _>>();
+    match numbers.as_deref() {
+        Ok([state, r, g, b]) => Ok(vec![(*state, [*r, *g, *b, 255])]),
+        Ok([r1, g1, b1, r2, g2, b2]) => {
+            let (from, to) = ([r1, g1, b1], [r2, g2, b2]);
+            let last = states - 1;
+            Ok((1..=last)
+                .map(|state| {
+                    let t = (state - 1) as f32 / (last - 1).max(1) as f32;
+                    let mut color = [255; 4];
+                    for c in 0..3 {
+                        color[c] = (*from[c] as f32 * (1. - t) + *to[c] as f32 * t).round() as u8;
+                    }
+                    (state as u8, color)
+                })
+                .collect())
+        }
+        _ => Err(format!("Invalid color \"{}\"", line)),
+    }
+}
+// Whether the neighbours can be matched to the sets in some order
+fn permutation_matches(sets: &[StateSet], neighbours: &[u8]) -> bool {
+    // Kuhn's augmenting paths, `matched[set]` is the neighbour taking that set
+    fn augment(
+        neighbour: usize,
+        sets: &[StateSet],
+        neighbours: &[u8],
+        matched: &mut [Option<usize>],
+        seen: &mut [bool],
+    ) -> bool {
+        for set in 0..sets.len() {
+            if seen[set] || !sets[set].contains(neighbours[neighbour]) {
+                continue;
+            }
+            seen[set] = true;
+            let free = match matched[set] {
+                Some(other) => augment(other, sets, neighbours, matched, seen),
+                None => true,
+            };
+            if free {
+                matched[set] = Some(neighbour);
+                return true;
+            }
+        }
+        false
+    }
+    let mut matched = vec![None; sets.len()];
+    (0..neighbours.len()).all(|neighbour| {
+        let mut seen = vec![false; sets.len()];
+        augment(neighbour, sets, neighbours, &mut matched, &mut seen)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // WireWorld as Golly ships it, a description under @RULE, comments and unbound variables
+    const WIREWORLD: &str = "@RULE WireWorld
+
+A 4-state CA created by Brian Silverman. WireWorld models the flow of
+currents in wires and makes it relatively easy to build logic gates.
+
+@TABLE
+
+# Golly rule-table format.
+# Each rule: C,N,NE,E,SE,S,SW,W,NW,C'
+# Default for transitions not listed: no change
+
+n_states:4
+neighborhood:Moore
+symmetries:permute
+
+var a={0,1,2,3}
+var b={0,1,2,3}
+var c={0,1,2,3}
+var d={0,1,2,3}
+var e={0,1,2,3}
+var f={0,1,2,3}
+var g={0,1,2,3}
+var h={0,1,2,3}
+var i={0,1,3}
+var j={0,1,3}
+var k={0,1,3}
+var l={0,1,3}
+var m={0,1,3}
+var n={0,1,3}
+var o={0,1,3}
+
+1,2,i,j,k,l,m,n,o,2 # a head and no other
+1,2,2,j,k,l,m,n,o,2 # two heads
+2,a,b,c,d,e,f,g,h,3 # heads become tails
+3,a,b,c,d,e,f,g,h,1 # tails become wires
+
+@COLORS
+
+# colors from
+# http://www.quinapalus.com/wi-index.html
+0 48 48 48   # dark gray
+1 255 128 0  # orange
+2 255 255 255  # white
+3 0 128 255  # light blue
+";
+
+    fn around(cell: u8, neighbours: &[u8]) -> [u8; MAX_INPUTS] {
+        let mut around = [0; MAX_INPUTS];
+        around[0] = cell;
+        around[1..1 + neighbours.len()].copy_from_slice(neighbours);
+        around
+    }
+
+    #[test]
+    fn golly_rule_file() {
+        let rule = parse(WIREWORLD).unwrap();
+        assert_eq!(rule.name, "WireWorld");
+        assert_eq!(rule.states, 4);
+        assert_eq!(rule.neighbourhood, Neighbourhood::Moore);
+        assert_eq!(rule.colors[1], (1, [255, 128, 0, 255]));
+        assert_eq!(rule.next(&around(2, &[1; 8])), 3);
+        assert_eq!(rule.next(&around(3, &[2; 8])), 1);
+        assert_eq!(rule.next(&around(1, &[0, 0, 0, 2, 0, 0, 0, 0])), 2);
+        assert_eq!(rule.next(&around(1, &[0, 2, 0, 0, 0, 3, 2, 1])), 2);
+        assert_eq!(rule.next(&around(1, &[2, 2, 2, 0, 0, 0, 0, 0])), 1);
+        assert_eq!(rule.next(&around(0, &[2; 8])), 0);
+    }
+
+    #[test]
+    fn gradient_spans_the_live_states() {
+        let text = "@RULE Fade\n@TABLE\nn_states:5\nneighborhood:vonNeumann\n0,1,0,0,0,1\n\
+                    @COLORS\n255 0 0 0 0 255\n0 10 20 30\n";
+        let rule = parse(text).unwrap();
+        assert_eq!(
+            rule.colors,
+            vec![
+                (1, [255, 0, 0, 255]),
+                (2, [170, 0, 85, 255]),
+                (3, [85, 0, 170, 255]),
+                (4, [0, 0, 255, 255]),
+                (0, [10, 20, 30, 255]),
+            ]
+        );
+    }
+
+    fn table(neighbourhood: &str, symmetries: &str, transition: &str) -> Result<RuleTable, String> {
+        parse(&format!(
+            "@RULE Test\n@TABLE\nn_states:2\nneighborhood:{}\nsymmetries:{}\n{}\n",
+            neighbourhood, symmetries, transition
+        ))
+    }
+
+    #[test]
+    fn symmetries_are_golly_s() {
+        for symmetries in [
+            "none",
+            "permute",
+            "reflect_horizontal",
+            "rotate4",
+            "rotate4reflect",
+        ] {
+            assert!(
+                table("vonNeumann", symmetries, "0,1,0,0,0,1").is_ok(),
+                "{}",
+                symmetries
+            );
+        }
+        for symmetries in ["rotate8", "rotate8reflect"] {
+            assert!(table("Moore", symmetries, "0,1,0,0,0,0,0,0,0,1").is_ok());
+            assert!(table("vonNeumann", symmetries, "0,1,0,0,0,1").is_err());
+        }
+        for symmetries in ["rotate2", "rotate3", "rotate6", "rotate6reflect"] {
+            assert!(table("hexagonal", symmetries, "0,1,0,0,0,0,0,1").is_ok());
+            assert!(table("Moore", symmetries, "0,1,0,0,0,0,0,0,0,1").is_err());
+        }
+        for symmetries in ["reflect", "rotate1", "rotate5", "rotate4reflect", "Rotate4"] {
+            assert!(table("hexagonal", symmetries, "0,1,0,0,0,0,0,1").is_err());
+        }
+    }
+
+    #[test]
+    fn symmetries_copy_the_transitions() {
+        // A cell is born with a single neighbour on a side
+        let rotate4 = table("Moore", "rotate4", "0,1,0,0,0,0,0,0,0,1").unwrap();
+        assert_eq!(rotate4.next(&around(0, &[0, 0, 1, 0, 0, 0, 0, 0])), 1);
+        assert_eq!(rotate4.next(&around(0, &[0, 0, 0, 0, 0, 0, 1, 0])), 1);
+        assert_eq!(rotate4.next(&around(0, &[0, 1, 0, 0, 0, 0, 0, 0])), 0);
+        // NE mirrors to NW but not to SE
+        let reflect = table("Moore", "reflect_horizontal", "0,0,1,0,0,0,0,0,0,1").unwrap();
+        assert_eq!(reflect.next(&around(0, &[0, 0, 0, 0, 0, 0, 0, 1])), 1);
+        assert_eq!(reflect.next(&around(0, &[0, 0, 0, 1, 0, 0, 0, 0])), 0);
+        // N turns to S by half a turn of the hexagon
+        let rotate2 = table("hexagonal", "rotate2", "0,1,0,0,0,0,0,1").unwrap();
+        assert_eq!(rotate2.next(&around(0, &[0, 0, 0, 1, 0, 0])), 1);
+        assert_eq!(rotate2.next(&around(0, &[0, 1, 0, 0, 0, 0])), 0);
+    }
+}
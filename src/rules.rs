@@ -0,0 +1,902 @@
+use bevy::prelude::*;
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use crate::hensel;
+use crate::lattice::{self, Lattice};
+use crate::rule_table::{RuleTable, MAX_INPUTS};
+use crate::universe::State;
+
+/**
+ *  The rule the universe evolves with, written in the usual notations so rules can be typed in and shared.
+ *  Life-like rules are written B3/S23 (or S/B, 23/3), Generations rules add the number of states: B2/S/C3,
+ *  or S/B/C as in /2/3. In Generations rules the cells that don't survive go through the dying states one
+ *  generation at a time before they're dead, and only living cells count as neighbours.
+ *  Isotropic non-totalistic rules tell apart the arrangements of the living neighbours, in Hensel's notation
+ *  (see hensel.rs) like B2-a/S12. They're looked up in a table of every arrangement of the 3 x 3 square.
+ *  Rules ending in H, like B2/S34H or B2o/S2m34H, count the 6 neighbours of the hexagonal lattice
+ *  and rules ending in L, like B4/S3456L, the 12 neighbours of the triangular lattice.
+ *  Larger than Life rules count the neighbours further away, they're written R5,C0,M1,S34..58,B34..45,NM:
+ *  the radius, the states, whether the cell counts itself, the survival and birth ranges and the neighbourhood.
+ *  One dimensional rules compute each generation as a new row under the last one: Wolfram's elementary rules
+ *  like W30 and the totalistic ones with more colours and a bigger radius, like T1599,K3,R1 (the code, the colours
+ *  and the cells on each side).
+ *  WireWorld simulates circuits: electron heads become tails, tails become conductors and conductors become heads
+ *  when 1 or 2 of their neighbours are heads (https://en.wikipedia.org/wiki/Wireworld).
+ *  Block rules cut the board into 2 x 2 blocks on the Margolus neighbourhood, shifted by one cell every other
+ *  generation, and replace each block by the one their table gives. They're written like in LifeViewer with the
+ *  16 blocks each block becomes, M0,8,4,3,2,5,9,7,1,6,10,11,12,13,14,15 for the billiard ball machine, or in
+ *  MCell's MS,D0;8;4;3;... The top left cell of a block counts 1, the top right 2, the bottom left 4 and the
+ *  bottom right 8. When no two blocks become the same one the rule is reversible and can run backwards.
+ *  Any other automaton can be loaded from a Golly .rule file (see rule_table.rs), it's then called by its name.
+ */
+
+// Most states a rule can have, a cell is a byte
+pub const MAX_STATES: u16 = 256;
+pub const MAX_RADIUS: i32 = 500;
+// Most cells on each side one dimensional rules look at
+const MAX_ROW_RADIUS: u32 = 10;
+// Most neighbours totalistic rules count, on the triangular lattice
+pub const MAX_NEIGHBOURS: usize = 12;
+// WireWorld's states after the electron heads, which are alive
+const ELECTRON_TAIL: State = State(2);
+const CONDUCTOR: State = State(3);
+// The usual colors of WireWorld's states: empty, electron head, electron tail and conductor
+const WIREWORLD_COLORS: [(u8, [u8; 4]); 4] = [
+    (0, [0, 0, 0, 255]),
+    (1, [0, 128, 255, 255]),
+    (2, [255, 48, 0, 255]),
+    (3, [255, 200, 0, 255]),
+];
+
+// Blocks of the Margolus neighbourhood, 2 x 2 cells
+pub const BLOCKS: usize = 16;
+
+// Rules that can be picked in the UI, any other one can be typed in
+pub const PRESETS: [(&str, &str); 26] = [
+    ("Conway's Life", "B3/S23"),
+    ("HighLife", "B36/S23"),
+    ("Seeds", "B2/S"),
+    ("Day & Night", "B3678/S34678"),
+    ("Brian's Brain", "B2/S/C3"),
+    ("Star Wars", "B2/S345/C4"),
+    ("Frogs", "B34/S12/C3"),
+    ("Bloomerang", "B34678/S234/C24"),
+    ("tlife", "B3/S2-i34q"),
+    ("B2-a/S12", "B2-a/S12"),
+    ("Hexagonal Life", "B2/S34H"),
+    ("B2o/S2m34H", "B2o/S2m34H"),
+    ("B4/S345L", "B4/S345L"),
+    ("Rule 30", "W30"),
+    ("Rule 90", "W90"),
+    ("Rule 110", "W110"),
+    ("Code 1599", "T1599,K3,R1"),
+    ("WireWorld", "WireWorld"),
+    ("Critters", "M15,14,13,3,11,5,6,1,7,9,10,2,12,4,8,0"),
+    ("Tron", "M15,1,2,3,4,5,6,7,8,9,10,11,12,13,14,0"),
+    (
+        "Billiard Ball Machine",
+        "M0,8,4,3,2,5,9,7,1,6,10,11,12,13,14,15",
+    ),
+    ("Single Rotation", "M0,2,8,3,1,5,6,7,4,9,10,11,12,13,14,15"),
+    ("Bosco's Rule", "R5,C0,M1,S34..58,B34..45,NM"),
+    ("Majority", "R4,C0,M1,S41..81,B41..81,NM"),
+    ("Waffle", "R7,C0,M1,S100..200,B75..170,NM"),
+    ("Bugsmovie", "R10,C0,M1,S123..212,B123..170,NM"),
+];
+
+// The cells around a cell that count as its neighbours
+#[derive(PartialEq, Debug, Copy, Clone, Default)]
+pub enum Neighbourhood {
+    #[default]
+    Moore, // The square around the cell
+    VonNeumann, // The diamond of the cells at most `radius` steps away
+    Circular,   // The cells whose centre is within `radius` + 0.5 of the cell's
+    Hexagonal,  // The 6 cells touching the cell on the hexagonal lattice, the radius is always 1
+    Triangular, // The 12 cells touching the cell on the triangular lattice, 3 by a side and 9 by a corner
+}
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub enum Rule {
+    // Births and survivals by the number of living neighbours, indexed by the count
+    Totalistic {
+        birth: [bool; MAX_NEIGHBOURS + 1],
+        survival: [bool; MAX_NEIGHBOURS + 1],
+        // Moore, von Neumann, hexagonal or triangular
+        neighbourhood: Neighbourhood,
+        // 2 for Life-like rules, more for Generations rules
+        states: u16,
+    },
+    // Whether a cell is alive on the next generation for each arrangement of the cell and its neighbours,
+    // the 3 x 3 square or the hexagon around it (see `Universe::arrangement`)
+    Isotropic {
+        table: Vec<bool>,
+        neighbourhood: Neighbourhood,
+        states: u16,
+    },
+    // Births and survivals by ranges of living cells in a bigger neighbourhood
+    LargerThanLife {
+        radius: i32,
+        neighbourhood: Neighbourhood,
+        // The cell itself counts as one of its neighbours
+        middle: bool,
+        birth: RangeInclusive<u32>,
+        survival: RangeInclusive<u32>,
+        states: u16,
+    },
+    // Looked up from the states of the cell and its neighbours
+    Table(Box<RuleTable>),
+    // One dimensional rules, each generation is a row computed from the row above (see `Universe::step`)
+    Wolfram {
+        code: u128,
+        // Elementary rules read the cells above as a binary number, totalistic ones add their states up
+        totalistic: bool,
+        // Cells on each side of the one above that count
+        radius: u32,
+        states: u16,
+        // The next state for each number or sum, the digits of the code in base `states`
+        table: Vec<u8>,
+    },
+    // Electrons running along wires, the heads are the living cells
+    WireWorld,
+    // Block rules, the block each block of the Margolus neighbourhood becomes (see `Universe::step`)
+    Margolus {
+        table: [u8; BLOCKS],
+    },
+}
+
+impl Neighbourhood {
+    // Counts the living cells around `cell`, `rect` counts the ones in a rectangle from its first to its last cell
+    pub fn count(
+        &self,
+        radius: i32,
+        cell: IVec2,
+        mut rect: impl FnMut(IVec2, IVec2) -> u32,
+    ) -> u32 {
+        match self {
+            Neighbourhood::Moore => return rect(cell - radius, cell + radius),
+            Neighbourhood::Hexagonal => {
+                // Odd rows are shifted right, so the two cells touched above and below start one column later
+                let start = cell.x - 1 + cell.y.rem_euclid(2);
+                return rect(cell - IVec2::X, cell + IVec2::X)
+                    + rect(
+                        IVec2::new(start, cell.y - 1),
+                        IVec2::new(start + 1, cell.y - 1),
+                    )
+                    + rect(
+                        IVec2::new(start, cell.y + 1),
+                        IVec2::new(start + 1, cell.y + 1),
+                    );
+            }
+            Neighbourhood::Triangular => {
+                // Five cells on the side of the base, three on the side of the apex
+                let (base, apex) = match lattice::points_up(cell) {
+                    true => (1, -1),
+                    false => (-1, 1),
+                };
+                return rect(cell - IVec2::new(2, 0), cell + IVec2::new(2, 0))
+                    + rect(cell + IVec2::new(-2, base), cell + IVec2::new(2, base))
+                    + rect(cell + IVec2::new(-1, apex), cell + IVec2::new(1, apex));
+            }
+            _ => {}
+        }
+        // One row at a time
+        (-radius..=radius)
+            .map(|dy| {
+                let half_width = match self {
+                    Neighbourhood::VonNeumann => radius - dy.abs(),
+                    _ => ((radius * radius + radius - dy * dy) as f32).sqrt() as i32,
+                };
+                rect(
+                    cell + IVec2::new(-half_width, dy),
+                    cell + IVec2::new(half_width, dy),
+                )
+            })
+            .sum()
+    }
+    // Cells around the cell when the radius is 1
+    pub fn size(&self) -> usize {
+        match self {
+            Neighbourhood::VonNeumann => 4,
+            Neighbourhood::Hexagonal => 6,
+            Neighbourhood::Triangular => 12,
+            _ => 8,
+        }
+    }
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        // https://en.wikipedia.org/wiki/Conway%27s_Game_of_Life#Rules
+        Rule::parse("B3/S23").unwrap()
+    }
+}
+
+impl Rule {
+    pub fn states(&self) -> u16 {
+        match self {
+            Rule::Totalistic { states, .. }
+            | Rule::Isotropic { states, .. }
+            | Rule::LargerThanLife { states, .. }
+            | Rule::Wolfram { states, .. } => *states,
+            Rule::Table(table) => table.states,
+            Rule::WireWorld => 4,
+            Rule::Margolus { .. } => 2,
+        }
+    }
+    // Colors some states must have, the others come from the palette
+    pub fn colors(&self) -> &[(u8, [u8; 4])] {
+        match self {
+            Rule::Table(table) => &table.colors,
+            Rule::WireWorld => &WIREWORLD_COLORS,
+            _ => &[],
+        }
+    }
+    // Cells are colored by their state instead of by the color mode
+    pub fn colored_by_state(&self) -> bool {
+        self.states() > 2 || !self.colors().is_empty()
+    }
+    // The cells counted as neighbours, with the radius of the neighbourhood
+    pub fn neighbourhood(&self) -> (Neighbourhood, i32) {
+        match self {
+            Rule::Totalistic { neighbourhood, .. } | Rule::Isotropic { neighbourhood, .. } => {
+                (*neighbourhood, 1)
+            }
+            Rule::LargerThanLife {
+                radius,
+                neighbourhood,
+                ..
+            } => (*neighbourhood, *radius),
+            Rule::Table(table) => (table.neighbourhood, 1),
+            // The heads among the 8 cells around
+            Rule::WireWorld => (Neighbourhood::Moore, 1),
+            // Only the block of the cell counts, see `Rule::next_block`
+            Rule::Margolus { .. } => (Neighbourhood::Moore, 1),
+            // Only the row above counts, see `Rule::next_in_row`
+            Rule::Wolfram { radius, .. } => (Neighbourhood::Moore, *radius as i32),
+        }
+    }
+    // Generations are rows of the board instead of the whole board
+    pub fn is_one_dimensional(&self) -> bool {
+        matches!(self, Rule::Wolfram { .. })
+    }
+    // Each generation can be computed back from the next one, see `Universe::step_back`
+    pub fn is_reversible(&self) -> bool {
+        match self {
+            Rule::Margolus { table } => (0..BLOCKS as u8).all(|block| table.contains(&block)),
+            _ => false,
+        }
+    }
+    // Empty cells come to life with nothing alive around them, so no part of the board stays empty on its own
+    // (see `Universe::runs`)
+    pub fn wakes_empty(&self) -> bool {
+        match self {
+            Rule::Table(table) => table.next(&[0; MAX_INPUTS]) != 0,
+            Rule::Margolus { .. } => self.next_block(0, false) != 0,
+            Rule::Wolfram { radius, .. } => {
+                let above = std::iter::repeat_n(State::DEAD, 2 * *radius as usize + 1);
+                self.next_in_row(above) != State::DEAD
+            }
+            _ => self.next(State::DEAD, 0) != State::DEAD,
+        }
+    }
+    // How the cells are laid out, hexagonal neighbourhoods need the hexagonal lattice
+    pub fn lattice(&self) -> Lattice {
+        match self.neighbourhood().0 {
+            Neighbourhood::Hexagonal => Lattice::Hexagonal,
+            Neighbourhood::Triangular => Lattice::Triangular,
+            _ => Lattice::Square,
+        }
+    }
+    // The state of a cell on the next generation given how many neighbours are alive (electron heads for WireWorld),
+    // or for isotropic rules the arrangement of the living cells. Tables look at the states around the cell instead,
+    // see `RuleTable::next`
+    pub fn next(&self, state: State, neighbours_alive: u32) -> State {
+        let (born, survives) = match self {
+            Rule::Totalistic {
+                birth, survival, ..
+            } => (
+                birth[neighbours_alive as usize],
+                survival[neighbours_alive as usize],
+            ),
+            Rule::Isotropic { table, .. } => {
+                let alive = table[neighbours_alive as usize];
+                (alive, alive)
+            }
+            Rule::LargerThanLife {
+                middle,
+                birth,
+                survival,
+                ..
+            } => {
+                let count = neighbours_alive + (*middle && state == State::ALIVE) as u32;
+                (birth.contains(&count), survival.contains(&count))
+            }
+            Rule::WireWorld => {
+                return match state {
+                    State::ALIVE => ELECTRON_TAIL,
+                    ELECTRON_TAIL => CONDUCTOR,
+                    CONDUCTOR if (1..=2).contains(&neighbours_alive) => State::ALIVE,
+                    _ => state,
+                }
+            }
+            Rule::Table(_) | Rule::Wolfram { .. } | Rule::Margolus { .. } => return state,
+        };
+        match state {
+            State::DEAD if born => State::ALIVE,
+            State::DEAD => State::DEAD,
+            State::ALIVE if survives => State::ALIVE,
+            // The last dying state wraps around to dead
+            State(n) => State(((n as u16 + 1) % self.states()) as u8),
+        }
+    }
+    // The state of a cell of a one dimensional rule from the cells above it, left to right
+    pub fn next_in_row(&self, above: impl Iterator<Item = State>) -> State {
+        let Rule::Wolfram {
+            totalistic, table, ..
+        } = self
+        else {
+            return State::DEAD;
+        };
+        let index = above.fold(0, |index, state| match totalistic {
+            true => index + state.0 as usize,
+            false => index * 2 + state.0 as usize,
+        });
+        State(table[index])
+    }
+    // The block a 2 x 2 block of a block rule becomes, or the one it comes from `backwards`. Blocks of rules
+    // that aren't reversible don't go back
+    pub fn next_block(&self, block: u8, backwards: bool) -> u8 {
+        let Rule::Margolus { table } = self else {
+            return block;
+        };
+        match backwards {
+            false => table[block as usize],
+            true => table
+                .iter()
+                .position(|next| *next == block)
+                .map_or(block, |previous| previous as u8),
+        }
+    }
+    // How the inspector calls a state
+    pub fn state_name(&self, state: State) -> String {
+        match self {
+            Rule::Table(_) => return format!("state {}", state.0),
+            Rule::Wolfram { states, .. } if *states > 2 => return format!("colour {}", state.0),
+            Rule::WireWorld => {
+                return String::from(match state {
+                    State::ALIVE => "electron head",
+                    ELECTRON_TAIL => "electron tail",
+                    CONDUCTOR => "conductor",
+                    _ => "empty",
+                })
+            }
+            _ => {}
+        }
+        match state {
+            State::DEAD => String::from("dead"),
+            State::ALIVE => String::from("alive"),
+            State(n) => format!("dying ({})", n - 1),
+        }
+    }
+
+    // Reads B3/S23, B2/S/C3 (or G3) and the S/B and S/B/C forms, letters can be lowercase. Like in Golly,
+    // a final H counts the neighbours on the hexagonal lattice and a final V in the von Neumann neighbourhood.
+    // Like in LifeViewer, a final L counts them on the triangular lattice with a, b and c for 10 to 12
+    pub fn parse(text: &str) -> Result<Rule, String> {
+        let mut text: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+        if text.starts_with(['R', 'r']) {
+            return parse_larger_than_life(&text);
+        }
+        if text.eq_ignore_ascii_case("WireWorld") {
+            return Ok(Rule::WireWorld);
+        }
+        if text.starts_with(['M', 'm']) {
+            return parse_margolus(&text);
+        }
+        if text.starts_with(['W', 'w', 'T', 't']) {
+            return parse_wolfram(&text);
+        }
+        let neighbourhood = match text.pop().map(|c| c.to_ascii_uppercase()) {
+            Some('H') => Neighbourhood::Hexagonal,
+            Some('V') => Neighbourhood::VonNeumann,
+            Some('L') => Neighbourhood::Triangular,
+            last => {
+                text.extend(last);
+                Neighbourhood::Moore
+            }
+        };
+        let parts: Vec<&str> = text.split('/').collect();
+        let (mut birth, mut survival, mut states) = (None, None, None);
+        if parts
+            .iter()
+            .any(|part| part.starts_with(char::is_alphabetic))
+        {
+            for (i, part) in parts.iter().enumerate() {
+                let mut chars = part.chars();
+                match chars.next().map(|c| c.to_ascii_uppercase()) {
+                    Some('B') => birth = Some(chars.as_str()),
+                    Some('S') => survival = Some(chars.as_str()),
+                    Some('C') | Some('G') => states = Some(chars.as_str()),
+                    // Golly also accepts B2/S/3
+                    Some(c) if c.is_ascii_digit() && i == 2 => states = Some(part),
+                    _ => return Err(format!("Unexpected \"{}\" in the rule", part)),
+                }
+            }
+        } else {
+            match parts[..] {
+                [s, b] => (survival, birth) = (Some(s), Some(b)),
+                [s, b, c] => (survival, birth, states) = (Some(s), Some(b), Some(c)),
+                _ => return Err(String::from("Rules look like B3/S23 or 23/3")),
+            }
+        }
+        let states = match states {
+            Some(states) => parse_states(states)?,
+            None => 2,
+        };
+        let (birth, survival) = (
+            birth.ok_or("The rule has no births (B)")?,
+            survival.ok_or("The rule has no survivals (S)")?,
+        );
+        // Triangular counts go over 9 and use letters
+        let arranged = neighbourhood != Neighbourhood::Triangular
+            && (birth.to_string() + survival).contains(|c: char| c.is_alphabetic() || c == '-');
+        if arranged {
+            if neighbourhood == Neighbourhood::VonNeumann {
+                return Err(String::from(
+                    "Von Neumann neighbours can only be counted, not arranged",
+                ));
+            }
+            // Survivals are the arrangements with the cell alive
+            let birth = hensel::parse(birth, neighbourhood)?;
+            let survival = hensel::parse(survival, neighbourhood)?;
+            let cell = hensel::cell(neighbourhood);
+            let table = (0..birth.len())
+                .map(|i| match i & cell {
+                    0 => birth[i],
+                    _ => survival[i & !cell],
+                })
+                .collect();
+            return Ok(Rule::Isotropic {
+                table,
+                neighbourhood,
+                states,
+            });
+        }
+        Ok(Rule::Totalistic {
+            birth: parse_counts(birth, neighbourhood)?,
+            survival: parse_counts(survival, neighbourhood)?,
+            neighbourhood,
+            states,
+        })
+    }
+}
+
+// Written the way `Rule::parse` reads it, B/S with /C only for Generations rules
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Rule::Totalistic {
+                birth,
+                survival,
+                neighbourhood,
+                states,
+            } => {
+                write!(f, "B{}/S{}", write_counts(birth), write_counts(survival))?;
+                if *states > 2 {
+                    write!(f, "/C{}", states)?;
+                }
+                write_suffix(f, *neighbourhood)
+            }
+            Rule::Isotropic {
+                table,
+                neighbourhood,
+                states,
+            } => {
+                let cell = hensel::cell(*neighbourhood);
+                let survival: Vec<bool> = (0..table.len()).map(|i| table[i | cell]).collect();
+                write!(
+                    f,
+                    "B{}/S{}",
+                    hensel::write(table, *neighbourhood),
+                    hensel::write(&survival, *neighbourhood)
+                )?;
+                if *states > 2 {
+                    write!(f, "/C{}", states)?;
+                }
+                write_suffix(f, *neighbourhood)
+            }
+            Rule::LargerThanLife {
+                radius,
+                neighbourhood,
+                middle,
+                birth,
+                survival,
+                states,
+            } => write!(
+                f,
+                "R{},C{},M{},S{}..{},B{}..{},N{}",
+                radius,
+                if *states > 2 { *states } else { 0 },
+                *middle as u8,
+                survival.start(),
+                survival.end(),
+                birth.start(),
+                birth.end(),
+                match neighbourhood {
+                    Neighbourhood::VonNeumann => 'N',
+                    Neighbourhood::Circular => 'C',
+                    _ => 'M',
+                }
+            ),
+            Rule::Table(table) => write!(f, "{}", table.name),
+            Rule::Wolfram {
+                code,
+                totalistic: false,
+                ..
+            } => write!(f, "W{}", code),
+            Rule::Wolfram {
+                code,
+                radius,
+                states,
+                ..
+            } => write!(f, "T{},K{},R{}", code, states, radius),
+            Rule::WireWorld => write!(f, "WireWorld"),
+            Rule::Margolus { table } => {
+                let blocks: Vec<String> = table.iter().map(u8::to_string).collect();
+                write!(f, "M{}", blocks.join(","))
+            }
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////
+/// UTILS
+////////////////////////////////////////////////////////////////////////
+
+// The neighbour counts in a part of a rule, like 23 in S23. Counts over 9 are written like hexadecimal digits
+fn parse_counts(
+    text: &str,
+    neighbourhood: Neighbourhood,
+) -> Result<[bool; MAX_NEIGHBOURS + 1], String> {
+    let mut counts = [false; MAX_NEIGHBOURS + 1];
+    let most = neighbourhood.size() as u32;
+    for c in text.chars() {
+        match c.to_digit(16) {
+            Some(count) if count <= most => counts[count as usize] = true,
+            _ => {
+                return Err(format!(
+                    "\"{}\" isn't a neighbour count from 0 to {}",
+                    c, most
+                ))
+            }
+        }
+    }
+    Ok(counts)
+}
+fn write_counts(counts: &[bool; MAX_NEIGHBOURS + 1]) -> String {
+    (0..=MAX_NEIGHBOURS as u32)
+        .filter(|&count| counts[count as usize])
+        .filter_map(|count| char::from_digit(count, 16))
+        .collect()
+}
+// The letter after rules that aren't counted in the Moore neighbourhood
+fn write_suffix(f: &mut fmt::Formatter, neighbourhood: Neighbourhood) -> fmt::Result {
+    match neighbourhood {
+        Neighbourhood::Hexagonal => write!(f, "H"),
+        Neighbourhood::VonNeumann => write!(f, "V"),
+        Neighbourhood::Triangular => write!(f, "L"),
+        _ => Ok(()),
+    }
+}
+// R5,C0,M1,S34..58,B34..45,NM, C0 and C2 both mean two states. The neighbourhood is Moore when it's left out
+fn parse_larger_than_life(text: &str) -> Result<Rule, String> {
+    let (mut radius, mut states, mut middle) = (None, 2, false);
+    let (mut birth, mut survival, mut neighbourhood) = (None, None, Neighbourhood::Moore);
+    for part in text.split(',') {
+        let mut chars = part.chars();
+        let key = chars.next().map(|c| c.to_ascii_uppercase());
+        let value = chars.as_str();
+        match key {
+            Some('R') => match value.parse::<i32>() {
+                Ok(r) if (1..=MAX_RADIUS).contains(&r) => radius = Some(r),
+                _ => return Err(format!("The radius goes from 1 to {}", MAX_RADIUS)),
+            },
+            Some('C') => {
+                states = match value {
+                    "0" | "1" => 2,
+                    _ => parse_states(value)?,
+                }
+            }
+            Some('M') => match value {
+                "0" => middle = false,
+                "1" => middle = true,
+                _ => return Err(String::from("M is 0 or 1")),
+            },
+            Some('S') => survival = Some(parse_range(value)?),
+            Some('B') => birth = Some(parse_range(value)?),
+            Some('N') => {
+                neighbourhood = match value.to_ascii_uppercase().as_str() {
+                    "M" => Neighbourhood::Moore,
+                    "N" => Neighbourhood::VonNeumann,
+                    "C" => Neighbourhood::Circular,
+                    _ => return Err(String::from("The neighbourhood is NM, NN or NC")),
+                }
+            }
+            _ => return Err(format!("Unexpected \"{}\" in the rule", part)),
+        }
+    }
+    Ok(Rule::LargerThanLife {
+        radius: radius.ok_or("The rule has no radius (R)")?,
+        neighbourhood,
+        middle,
+        birth: birth.ok_or("The rule has no births (B)")?,
+        survival: survival.ok_or("The rule has no survivals (S)")?,
+        states,
+    })
+}
+// W30 for elementary rules, T1599,K3,R1 for totalistic ones with the number of colours and the radius,
+// 2 and 1 when they're left out
+fn parse_wolfram(text: &str) -> Result<Rule, String> {
+    let mut parts = text.split(',');
+    let first = parts.next().unwrap_or_default();
+    let totalistic = first.starts_with(['T', 't']);
+    let code = first[1..]
+        .parse::<u128>()
+        .map_err(|_| format!("\"{}\" isn't a rule number", &first[1..]))?;
+    let (mut states, mut radius) = (2, 1);
+    for part in parts {
+        let mut chars = part.chars();
+        let key = chars.next().map(|c| c.to_ascii_uppercase());
+        match (key, totalistic) {
+            (Some('K'), true) => states = parse_states(chars.as_str())?,
+            (Some('R'), true) => match chars.as_str().parse::<u32>() {
+                Ok(r) if (1..=MAX_ROW_RADIUS).contains(&r) => radius = r,
+                _ => return Err(format!("The radius goes from 1 to {}", MAX_ROW_RADIUS)),
+            },
+            _ => return Err(format!("Unexpected \"{}\" in the rule", part)),
+        }
+    }
+    // One digit of the code per sum of the cells above, or per arrangement of them
+    let cells = 2 * radius + 1;
+    let digits = match totalistic {
+        true => cells * (states as u32 - 1) + 1,
+        false => 1 << cells,
+    };
+    let codes = (states as u128)
+        .checked_pow(digits)
+        .ok_or("There are too many rules with these colours and radius")?;
+    if code >= codes {
+        return Err(format!("The rule number goes from 0 to {}", codes - 1));
+    }
+    let table = (0..digits)
+        .scan(code, |rest, _| {
+            let digit = (*rest % states as u128) as u8;
+            *rest /= states as u128;
+            Some(digit)
+        })
+        .collect();
+    Ok(Rule::Wolfram {
+        code,
+        totalistic,
+        radius,
+        states,
+        table,
+    })
+}
+// A range of neighbour counts, like 34..58 in S34..58
+// M0,8,4,3,2,5,9,7,1,6,10,11,12,13,14,15 or MS,D0;8;4;3;2;5;9;7;1;6;10;11;12;13;14;15
+fn parse_margolus(text: &str) -> Result<Rule, String> {
+    let upper = text.to_ascii_uppercase();
+    let blocks: Vec<&str> = match upper.strip_prefix("MS,D") {
+        Some(blocks) => blocks.split(';').collect(),
+        None => upper[1..].split(',').collect(),
+    };
+    let mut table = [0; BLOCKS];
+    if blocks.len() != BLOCKS {
+        return Err(format!(
+            "Block rules give the block each of the {} blocks becomes",
+            BLOCKS
+        ));
+    }
+    for (next, block) in table.iter_mut().zip(blocks) {
+        *next = block
+            .parse()
+            .ok()
+            .filter(|block| (*block as usize) < BLOCKS)
+            .ok_or_else(|| format!("\"{}\" isn't a block, they're 0 to 15", block))?;
+    }
+    Ok(Rule::Margolus { table })
+}
+fn parse_range(text: &str) -> Result<RangeInclusive<u32>, String> {
+    let invalid = || format!("\"{}\" isn't a range like 34..58", text);
+    let (start, end) = text.split_once("..").ok_or_else(invalid)?;
+    let start = start.parse::<u32>().map_err(|_| invalid())?;
+    let end = end.parse::<u32>().map_err(|_| invalid())?;
+    Ok(start..=end)
+}
+fn parse_states(text: &str) -> Result<u16, String> {
+    match text.parse::<u16>() {
+        Ok(states) if (2..=MAX_STATES).contains(&states) => Ok(states),
+        _ => Err(format!(
+            "The number of states goes from 2 to {}",
+            MAX_STATES
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::universe::Universe;
+
+    #[test]
+    fn isotropic_rules_are_written_as_read() {
+        for text in ["B2-a/S12", "B3/S2-i34q", "B2o/S2m34H", "B2ce3/S23/C3"] {
+            let rule = Rule::parse(text).unwrap();
+            assert!(matches!(rule, Rule::Isotropic { .. }), "{}", text);
+            assert_eq!(rule.to_string(), text);
+        }
+        // Lowercase and every letter of a count
+        let rule = Rule::parse("b2ceaikn3/s23").unwrap();
+        assert_eq!(rule.to_string(), "B23/S23");
+        assert!(Rule::parse("B2a/S2V").is_err());
+    }
+
+    #[test]
+    fn isotropic_rules_look_at_the_arrangement() {
+        // tlife: cells with 2 neighbours survive unless they're on opposite edges
+        let rule = Rule::parse("B3/S2-i34q").unwrap();
+        let survives = |cells: &[(i32, i32)]| {
+            let mut universe = Universe::new(3, 3);
+            universe.set(1, 1, State::ALIVE);
+            for (x, y) in cells {
+                universe.set(*x, *y, State::ALIVE);
+            }
+            universe.next_state(&rule, 1, 1) == State::ALIVE
+        };
+        assert!(!survives(&[(1, 0), (1, 2)]));
+        assert!(!survives(&[(0, 1), (2, 1)]));
+        assert!(survives(&[(0, 0), (2, 2)]));
+        assert!(survives(&[(1, 0), (2, 1)]));
+
+        // B2o/S2m34H, births need two touching neighbours on the hexagon
+        let rule = Rule::parse("B2o/S2m34H").unwrap();
+        let Rule::Isotropic { table, .. } = &rule else {
+            panic!("B2o/S2m34H isn't isotropic");
+        };
+        assert!(table[0b11]);
+        assert!(table[0b110000]);
+        assert!(!table[0b101]);
+        assert!(!table[0b1001]);
+        let cell = hensel::cell(Neighbourhood::Hexagonal);
+        assert!(table[cell | 0b101]);
+        assert!(!table[cell | 0b11]);
+        assert!(table[cell | 0b1111]);
+    }
+
+    #[test]
+    fn every_letter_is_the_totalistic_rule() {
+        let totalistic = Rule::parse("B3/S23").unwrap();
+        let isotropic = Rule::parse("B3ceaiknjqry/S2ceaikn3ceaiknjqry").unwrap();
+        assert!(matches!(isotropic, Rule::Isotropic { .. }));
+        let mut universe = Universe::new(16, 16);
+        for (x, y) in [
+            (1, 0),
+            (2, 1),
+            (0, 2),
+            (1, 2),
+            (2, 2),
+            (8, 8),
+            (9, 8),
+            (10, 8),
+        ] {
+            universe.set(x, y, State::ALIVE);
+        }
+        let mut other = universe.clone();
+        for _ in 0..20 {
+            universe.step(&totalistic);
+            other.step(&isotropic);
+            for y in 0..16 {
+                for x in 0..16 {
+                    assert_eq!(universe.get(x, y), other.get(x, y));
+                }
+            }
+        }
+    }
+
+    // The rows a one dimensional rule grows from a single cell in `state`, dead cells as dots
+    fn rows_from_one_cell(text: &str, state: u8, generations: usize) -> Vec<String> {
+        let rule = Rule::parse(text).unwrap();
+        let width = 11;
+        let mut universe = Universe::new(width as u32, generations as u32 + 1);
+        universe.set(width / 2, 0, State(state));
+        for _ in 0..generations {
+            universe.step(&rule);
+        }
+        (0..=generations as i32)
+            .map(|y| {
+                (0..width)
+                    .map(|x| match universe.get(x, y).unwrap() {
+                        State::DEAD => '.',
+                        State(n) => char::from_digit(n as u32, 10).unwrap(),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn elementary_rules_from_one_cell() {
+        assert_eq!(
+            rows_from_one_cell("W30", 1, 3),
+            [".....1.....", "....111....", "...11..1...", "..11.1111.."]
+        );
+        // Sierpinski's triangle
+        assert_eq!(
+            rows_from_one_cell("W90", 1, 4),
+            [
+                ".....1.....",
+                "....1.1....",
+                "...1...1...",
+                "..1.1.1.1..",
+                ".1.......1.",
+            ]
+        );
+        // Only grows to the left
+        assert_eq!(
+            rows_from_one_cell("W110", 1, 4),
+            [
+                ".....1.....",
+                "....11.....",
+                "...111.....",
+                "..11.1.....",
+                ".11111.....",
+            ]
+        );
+    }
+
+    #[test]
+    fn totalistic_rules_with_colours_and_radius() {
+        // 1599 is 2012020 in base 3, the next state for the sums 6 down to 0
+        assert_eq!(
+            rows_from_one_cell("T1599,K3,R1", 1, 3),
+            [".....1.....", "....222....", "....121....", "...22122..."]
+        );
+        // 10 is 001010 in base 2, cells with 1 or 3 living cells within 2 are born
+        assert_eq!(
+            rows_from_one_cell("T10,K2,R2", 1, 2),
+            [".....1.....", "...11111...", ".1.1...1.1."]
+        );
+        assert_eq!(
+            Rule::parse("t1599,k3,r1").unwrap().to_string(),
+            "T1599,K3,R1"
+        );
+        assert!(Rule::parse("T2187,K3,R1").is_err());
+        assert!(Rule::parse("W256").is_err());
+    }
+
+    #[test]
+    fn some_rules_wake_empty_cells() {
+        for text in [
+            "B3/S23",
+            "B2/S/C3",
+            "B2/S34H",
+            "WireWorld",
+            "W30",
+            "R5,C0,M1,S34..58,B34..45,NM",
+            "M0,8,4,3,2,5,9,7,1,6,10,11,12,13,14,15",
+        ] {
+            assert!(!Rule::parse(text).unwrap().wakes_empty(), "{}", text);
+        }
+        for text in [
+            "B0/S8",
+            "B0123478/S01234678",
+            "W1",
+            "R2,C0,M0,S0..3,B0..3,NM",
+            "M15,14,13,3,11,5,6,1,7,9,10,2,12,4,8,0",
+        ] {
+            assert!(Rule::parse(text).unwrap().wakes_empty(), "{}", text);
+        }
+    }
+}
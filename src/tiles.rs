@@ -0,0 +1,467 @@
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+    sprite::MaterialMesh2dBundle,
+    window::PrimaryWindow,
+};
+use std::collections::{HashMap, HashSet};
+
+use crate::board_material::{BoardMaterial, BoardPalette};
+use crate::camera::MainCamera;
+use crate::game_of_life::{BoardSize, GameSettings, LastStep};
+use crate::grid;
+use crate::palette::{self, ColorMode, MAX_LEVEL};
+use crate::rules::{Rule, MAX_STATES};
+use crate::universe::{Universe, CHUNK_SIZE};
+
+/**
+ *  The board is drawn as square tiles of cells, each with its own textures, so its size isn't limited by the
+ *  biggest texture the GPU can hold. Only the tiles on screen exist. Each tile is a chunk of the universe, which
+ *  records when the cells of its chunks change (see `Universe::last_change`), so a tile is only encoded and
+ *  uploaded again when the chunks it shows, or the ones its colors depend on, changed.
+ *  The texture of a tile also holds the cells around it, so cells that aren't square can be drawn across the edge.
+ *  Zoomed far out, a downsampled density overview of the whole board is drawn instead of the tiles.
+ */
+
+// Cells per side of a tile, a tile shows a chunk of the universe
+const TILE_SIZE: i32 = CHUNK_SIZE;
+// Cells of the neighbouring tiles around the texture of a tile, hexagons poke out of their row and column
+const APRON: i32 = 1;
+const OVERVIEW_CELL_PIXELS: f32 = 0.5; // Below this size on screen the overview replaces the tiles
+const MAX_OVERVIEW_SIZE: u32 = 2048; // Texels per side of the overview
+const OVERVIEW_INTERVAL: f64 = 0.25; // Seconds between updates of the overview while the cells change
+
+#[derive(Component)]
+pub struct Tile {
+    // The first cell of the tile is at `index * TILE_SIZE`
+    index: IVec2,
+    // The previous texture holds the same generation as the current one
+    settled: bool,
+    // `Universe::revision` and `Universe::steps` when it was last encoded
+    revision: u64,
+    step: u64,
+}
+#[derive(Component)]
+pub struct Overview {
+    // Cells per side of a texel, 0 when the overview isn't drawn
+    block: u32,
+    // Texels per side
+    size: UVec2,
+    // `Universe::revision` when it was last updated
+    revision: u64,
+}
+
+#[derive(Resource)]
+pub struct BoardTiles {
+    tiles: HashMap<IVec2, Entity>,
+    quad: Handle<Mesh>,
+    // Color mode and generation the tiles were last encoded with
+    encoded: Option<(ColorMode, u64)>,
+    // Colors of the states of the rule, shared by every tile
+    state_colors: Handle<Image>,
+}
+
+////////////////////////////////////////////////////////////////////////
+/// SYSTEMS
+////////////////////////////////////////////////////////////////////////
+
+pub fn setup(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<BoardMaterial>>,
+    settings: Res<GameSettings>,
+    rule: Res<Rule>,
+) {
+    // Every tile is a unit quad scaled to its size
+    let quad = meshes.add(shape::Quad::new(Vec2::ONE).into());
+    let texture = images.add(cells_image(1, 1));
+    let state_colors = images.add(state_colors_image(&settings, &rule));
+    commands
+        .spawn(MaterialMesh2dBundle {
+            mesh: quad.clone().into(),
+            material: materials.add(BoardMaterial {
+                palette: BoardPalette::new(&settings, &rule, 1., 0.).overview(),
+                cells: texture.clone(),
+                previous: texture,
+                state_colors: state_colors.clone(),
+            }),
+            visibility: Visibility::Hidden,
+            ..default()
+        })
+        .insert(Overview {
+            block: 0,
+            size: UVec2::ZERO,
+            revision: 0,
+        });
+    commands.insert_resource(BoardTiles {
+        tiles: HashMap::new(),
+        quad,
+        encoded: None,
+        state_colors,
+    });
+}
+
+// Spawns the tiles that come on screen, despawns the ones that leave it and uploads the ones whose cells changed
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn update_tiles(
+    mut commands: Commands,
+    mut board_tiles: ResMut<BoardTiles>,
+    mut q_tiles: Query<(&mut Tile, &mut Transform, &Handle<BoardMaterial>)>,
+    q_camera: Query<(&Transform, &OrthographicProjection), (With<MainCamera>, Without<Tile>)>,
+    q_win: Query<&Window, With<PrimaryWindow>>,
+    universe: Res<Universe>,
+    rule: Res<Rule>,
+    settings: Res<GameSettings>,
+    board_size: Res<BoardSize>,
+    last_step: Res<LastStep>,
+    time: Res<Time>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<BoardMaterial>>,
+) {
+    let (camera, projection) = q_camera.single();
+    let view = grid::visible_cells(camera, projection, q_win.single(), &board_size, &settings);
+
+    // The tiles on screen, none when the overview is drawn
+    let mut visible = HashSet::new();
+    if view.cell_pixels >= OVERVIEW_CELL_PIXELS {
+        let tile_of =
+            |cell: IVec2| IVec2::new(cell.x.div_euclid(TILE_SIZE), cell.y.div_euclid(TILE_SIZE));
+        let last = tile_of(IVec2::new(
+            board_size.rows as i32 - 1,
+            board_size.columns as i32 - 1,
+        ));
+        let min = tile_of(view.min).max(IVec2::ZERO);
+        let max = tile_of(view.max).min(last);
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                visible.insert(IVec2::new(x, y));
+            }
+        }
+    }
+    // Tiles of a resized board don't line up with the cells anymore, the textures go with the materials
+    let resized = board_size.is_changed();
+    board_tiles.tiles.retain(|index, entity| {
+        let keep = visible.contains(index) && !resized;
+        if !keep {
+            commands.entity(*entity).despawn();
+        }
+        keep
+    });
+
+    let progress = if settings.interpolate {
+        let since_step = (time.elapsed_seconds_f64() - last_step.0) as f32;
+        (since_step / settings.time_step_secs).clamp(0., 1.)
+    } else {
+        1.
+    };
+    let palette = BoardPalette::new(&settings, &rule, progress, view.cell_pixels);
+    if settings.is_changed() || rule.is_changed() {
+        let colors = state_colors_image(&settings, &rule);
+        if images
+            .get(&board_tiles.state_colors)
+            .is_some_and(|image| image.data != colors.data)
+        {
+            *images.get_mut(&board_tiles.state_colors).unwrap() = colors;
+        }
+    }
+    // A new rule can change how cells are encoded
+    let mode_changed =
+        board_tiles.encoded.map(|(mode, _)| mode) != Some(settings.color_mode) || rule.is_changed();
+    let stepped =
+        board_tiles.encoded.map(|(_, generation)| generation) != Some(universe.generation());
+    let reencode = universe.is_changed() || mode_changed;
+    board_tiles.encoded = Some((settings.color_mode, universe.generation()));
+    let reach = reach(&rule, settings.color_mode);
+    let age_span = age_span(&rule, settings.color_mode);
+
+    let mut data = vec![];
+    for (mut tile, mut transform, handle) in q_tiles.iter_mut() {
+        if !board_tiles.tiles.contains_key(&tile.index) {
+            continue;
+        }
+        let (origin, size) = tile_rect(tile.index, &board_size);
+        let new_transform = rect_transform(origin, size, &board_size, settings.cell_size);
+        if *transform != new_transform {
+            *transform = new_transform;
+        }
+        // Only touch the material when something changed, that makes it upload again
+        if materials.get(handle).is_some_and(|m| m.palette != palette) {
+            materials.get_mut(handle).unwrap().palette = palette;
+        }
+        if !reencode {
+            continue;
+        }
+        let change = universe.last_change(origin - reach, origin + size.as_ivec2() - 1 + reach);
+        // The ages shown were still counting when it was encoded
+        let aged = universe.steps() > tile.step
+            && change.revision > 0
+            && age_span.is_some_and(|span| tile.step < change.step + span);
+        let dirty = mode_changed || change.revision > tile.revision || aged;
+        tile.revision = universe.revision();
+        tile.step = universe.steps();
+        let Some(material) = materials.get(handle) else {
+            continue;
+        };
+        if dirty {
+            encode_tile(
+                &universe,
+                &rule,
+                origin,
+                size,
+                settings.color_mode,
+                &mut data,
+            );
+            let cells = if stepped && !mode_changed {
+                // The generation on screen becomes the previous one, the one before it gets overwritten
+                let material = materials.get_mut(handle).unwrap();
+                std::mem::swap(&mut material.cells, &mut material.previous);
+                tile.settled = false;
+                material.cells.clone()
+            } else {
+                // Edits that aren't a new generation show up straight away
+                if let Some(previous) = images.get_mut(&material.previous) {
+                    previous.data.clone_from(&data);
+                }
+                tile.settled = true;
+                material.cells.clone()
+            };
+            if let Some(image) = images.get_mut(&cells) {
+                image.data.clone_from(&data);
+            }
+        } else if stepped && !tile.settled {
+            // Nothing changed on this generation, stop fading from the one before
+            if let Some(cells) = images.get(&material.cells) {
+                data.clone_from(&cells.data);
+            }
+            if let Some(previous) = images.get_mut(&material.previous) {
+                previous.data.clone_from(&data);
+            }
+            tile.settled = true;
+        }
+    }
+
+    // The new tiles start settled on the current generation
+    for index in visible {
+        if board_tiles.tiles.contains_key(&index) {
+            continue;
+        }
+        let (origin, size) = tile_rect(index, &board_size);
+        let apron = UVec2::splat(APRON as u32 * 2);
+        let mut image = cells_image(size.x + apron.x, size.y + apron.y);
+        encode_tile(
+            &universe,
+            &rule,
+            origin,
+            size,
+            settings.color_mode,
+            &mut image.data,
+        );
+        let entity = commands
+            .spawn(MaterialMesh2dBundle {
+                mesh: board_tiles.quad.clone().into(),
+                material: materials.add(BoardMaterial {
+                    palette,
+                    previous: images.add(image.clone()),
+                    cells: images.add(image),
+                    state_colors: board_tiles.state_colors.clone(),
+                }),
+                transform: rect_transform(origin, size, &board_size, settings.cell_size),
+                ..default()
+            })
+            .insert(Tile {
+                index,
+                settled: true,
+                revision: universe.revision(),
+                step: universe.steps(),
+            })
+            .id();
+        board_tiles.tiles.insert(index, entity);
+    }
+}
+
+// Shows the density of the whole board when zoomed out too far for the tiles, with about one texel per pixel
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn update_overview(
+    mut q_overview: Query<(
+        &mut Overview,
+        &mut Visibility,
+        &mut Transform,
+        &Handle<BoardMaterial>,
+    )>,
+    q_camera: Query<(&Transform, &OrthographicProjection), (With<MainCamera>, Without<Overview>)>,
+    q_win: Query<&Window, With<PrimaryWindow>>,
+    universe: Res<Universe>,
+    rule: Res<Rule>,
+    settings: Res<GameSettings>,
+    board_size: Res<BoardSize>,
+    time: Res<Time>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<BoardMaterial>>,
+    mut last_update: Local<f64>,
+) {
+    let (camera, projection) = q_camera.single();
+    let view = grid::visible_cells(camera, projection, q_win.single(), &board_size, &settings);
+    let (mut overview, mut visibility, mut transform, handle) = q_overview.single_mut();
+    if view.cell_pixels >= OVERVIEW_CELL_PIXELS {
+        if *visibility != Visibility::Hidden {
+            *visibility = Visibility::Hidden;
+            overview.block = 0;
+        }
+        return;
+    }
+    if *visibility != Visibility::Visible {
+        *visibility = Visibility::Visible;
+    }
+
+    let palette = BoardPalette::new(&settings, &rule, 1., view.cell_pixels).overview();
+    if materials.get(handle).is_some_and(|m| m.palette != palette) {
+        materials.get_mut(handle).unwrap().palette = palette;
+    }
+    // Blocks are a power of two so zooming doesn't recompute the overview on every step
+    let longest_side = board_size.rows.max(board_size.columns);
+    let block = ((1. / view.cell_pixels).ceil() as u32)
+        .max(longest_side.div_ceil(MAX_OVERVIEW_SIZE))
+        .next_power_of_two();
+    let size = UVec2::new(
+        universe.width().div_ceil(block),
+        universe.height().div_ceil(block),
+    );
+    let cells_per_texel = (block * block) as f32;
+    // Any living cell should be visible even when the block is mostly empty
+    let density = |alive: u32| ((alive as f32 / cells_per_texel).sqrt() * 255.).round() as u8;
+
+    if block != overview.block || size != overview.size {
+        // Every texel changes with the zoom or the size of the board
+        let (_, _, alive) = universe.alive_per_block(block);
+        let mut image = cells_image(size.x, size.y);
+        for (texel, count) in image.data.iter_mut().zip(alive) {
+            *texel = density(count);
+        }
+        *transform = rect_transform(IVec2::ZERO, size * block, &board_size, settings.cell_size);
+        if let Some(material) = materials.get_mut(handle) {
+            material.cells = images.add(image);
+            material.previous = material.cells.clone();
+        }
+        *overview = Overview {
+            block,
+            size,
+            revision: universe.revision(),
+        };
+        *last_update = time.elapsed_seconds_f64();
+        return;
+    }
+
+    // Only the texels over the chunks that changed are counted again
+    let now = time.elapsed_seconds_f64();
+    if universe.revision() == overview.revision || now - *last_update < OVERVIEW_INTERVAL {
+        return;
+    }
+    *last_update = now;
+    let mut texels = HashSet::new();
+    for chunk in universe.changed_chunks(overview.revision) {
+        let min = chunk * TILE_SIZE / block as i32;
+        let max = ((chunk + 1) * TILE_SIZE - 1) / block as i32;
+        let max = max.min(size.as_ivec2() - 1);
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                texels.insert(IVec2::new(x, y));
+            }
+        }
+    }
+    overview.revision = universe.revision();
+    let Some(image) = materials
+        .get(handle)
+        .and_then(|material| images.get_mut(&material.cells))
+    else {
+        return;
+    };
+    for texel in texels {
+        let first = texel * block as i32;
+        let alive = universe.alive_in(first, first + block as i32 - 1);
+        image.data[(texel.y * size.x as i32 + texel.x) as usize] = density(alive);
+    }
+}
+
+////////////////////////////////////////////////////////////////////////
+/// UTILS
+////////////////////////////////////////////////////////////////////////
+
+// Cells around a tile whose changes can change how it's drawn: the apron, and the neighbours of the cells
+// when they're colored by how many there are or by what happens to the cells next
+fn reach(rule: &Rule, color_mode: ColorMode) -> IVec2 {
+    // Triangles reach one cell further along their row
+    let neighbours = rule.neighbourhood().1 + 1;
+    let reach = match color_mode {
+        _ if rule.colored_by_state() => APRON,
+        ColorMode::Neighbours | ColorMode::Dying => APRON + neighbours,
+        _ => APRON,
+    };
+    IVec2::splat(reach)
+}
+// Steps after a change during which the cells it changed are drawn differently on each step, as their age goes up
+fn age_span(rule: &Rule, color_mode: ColorMode) -> Option<u64> {
+    match color_mode {
+        _ if rule.colored_by_state() => None,
+        ColorMode::Age => Some(MAX_LEVEL as u64),
+        ColorMode::BirthSurvival => Some(1),
+        _ => None,
+    }
+}
+// First cell and size in cells of a tile, the tiles on the right and bottom edges can be smaller
+fn tile_rect(index: IVec2, board_size: &BoardSize) -> (IVec2, UVec2) {
+    let origin = index * TILE_SIZE;
+    let board = IVec2::new(board_size.rows as i32, board_size.columns as i32);
+    let size = (board - origin).min(IVec2::splat(TILE_SIZE));
+    (origin, size.as_uvec2())
+}
+// Places a unit quad over a rectangle of cells
+fn rect_transform(origin: IVec2, size: UVec2, board_size: &BoardSize, cell_size: u8) -> Transform {
+    let half = board_size.world_size(cell_size) / 2.;
+    let center = (origin.as_vec2() + size.as_vec2() / 2.) * cell_size as f32;
+    Transform::from_xyz(center.x - half.x, half.y - center.y, 0.)
+        .with_scale((size.as_vec2() * cell_size as f32).extend(1.))
+}
+// Writes the cells of a tile as they're stored in its texture, row by row with the apron around them
+fn encode_tile(
+    universe: &Universe,
+    rule: &Rule,
+    origin: IVec2,
+    size: UVec2,
+    color_mode: ColorMode,
+    data: &mut Vec<u8>,
+) {
+    data.clear();
+    for y in origin.y - APRON..origin.y + size.y as i32 + APRON {
+        for x in origin.x - APRON..origin.x + size.x as i32 + APRON {
+            data.push(palette::encode_cell(universe, rule, x, y, color_mode));
+        }
+    }
+}
+fn cells_image(width: u32, height: u32) -> Image {
+    Image::new_fill(
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0],
+        TextureFormat::R8Unorm,
+    )
+}
+// One texel per state, the states the rule doesn't have are dead
+fn state_colors_image(settings: &GameSettings, rule: &Rule) -> Image {
+    let mut colors = palette::state_colors(&settings.palette, rule);
+    colors.resize(MAX_STATES as usize, settings.palette.dead);
+    Image::new(
+        Extent3d {
+            width: MAX_STATES as u32,
+            height: 1,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        colors.concat(),
+        TextureFormat::Rgba8Unorm,
+    )
+}
@@ -0,0 +1,365 @@
+use bevy::prelude::*;
+use std::fmt;
+
+use crate::game_of_life::{BoardSize, GameSettings, Tick};
+use crate::rules::Rule;
+use crate::universe::{State, Universe};
+
+/**
+ *  Turmites are ants walking on the board (https://en.wikipedia.org/wiki/Turmite). Each one is an entity with
+ *  a cell, a heading and a state. On every step it looks up its state and the color of its cell (the state of
+ *  the cell) in the transition table, recolors the cell, turns and moves forward by one cell.
+ *  Tables are written like in Golly, {{{1,2,0},{0,8,0}}} is Langton's ant: for each state and each color, the
+ *  color written, the turn (1 none, 2 right, 4 back, 8 left) and the next state. Ants with a single state can
+ *  also be written with a turn per color, RL for Langton's ant, each color is then replaced by the next one.
+ *  Turmites walk the board as a grid of squares whatever the lattice and come back on the other side of the edges.
+ *  Colors the rule has no state for are written as the ones it has, cycling, so the board only holds its states.
+ */
+
+pub const MAX_TURMITE_STATES: usize = 16;
+pub const MAX_TURMITE_COLORS: usize = 16;
+// Tables that can be picked in the UI
+pub const PRESETS: [(&str, &str); 5] = [
+    ("Langton's ant", "RL"),
+    ("LLRR", "LLRR"),
+    ("LRRRRRLLR", "LRRRRRLLR"),
+    ("RRLLLRLLLRRR", "RRLLLRLLLRRR"),
+    (
+        "Turmite 180121020081",
+        "{{{1,8,0},{1,2,1}},{{0,2,0},{0,8,1}}}",
+    ),
+];
+// Hue between the colors of two turmites spawned one after the other, the golden angle spreads them evenly
+const HUE_STEP: f32 = 137.5;
+
+#[derive(Default)]
+pub struct Turmites;
+
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Turmite {
+    pub cell: IVec2,
+    pub heading: Heading,
+    pub state: u8,
+    // Color it's drawn with
+    pub color: Color,
+}
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub enum Heading {
+    #[default]
+    Up,
+    Right,
+    Down,
+    Left,
+}
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub enum Turn {
+    #[default]
+    None,
+    Right,
+    Back,
+    Left,
+}
+// What a turmite does on a cell of some color
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub struct Transition {
+    pub color: u8,
+    pub turn: Turn,
+    pub state: u8,
+}
+// The transitions of each state of the turmites, for each color
+#[derive(PartialEq, Debug, Clone)]
+pub struct TurmiteTable(pub Vec<Vec<Transition>>);
+#[derive(Resource, PartialEq, Debug, Clone)]
+pub struct TurmiteSettings {
+    // Shared by every turmite so editing it changes how they all walk
+    pub table: TurmiteTable,
+    // Left clicks on the board spawn turmites instead of painting
+    pub placing: bool,
+    // The rule doesn't step while there are turmites
+    pub freeze_board: bool,
+    // Turmites spawned so far, each one gets the next color
+    pub spawned: u32,
+}
+
+impl Plugin for Turmites {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(TurmiteSettings::default())
+            .add_systems(Update, walk)
+            .add_systems(PostUpdate, draw);
+    }
+}
+impl Default for TurmiteSettings {
+    fn default() -> Self {
+        TurmiteSettings {
+            table: TurmiteTable::parse(PRESETS[0].1).unwrap(),
+            placing: false,
+            freeze_board: true,
+            spawned: 0,
+        }
+    }
+}
+impl Heading {
+    fn turned(self, turn: Turn) -> Heading {
+        let quarters = match turn {
+            Turn::None => 0,
+            Turn::Right => 1,
+            Turn::Back => 2,
+            Turn::Left => 3,
+        };
+        match (self as u8 + quarters) % 4 {
+            0 => Heading::Up,
+            1 => Heading::Right,
+            2 => Heading::Down,
+            _ => Heading::Left,
+        }
+    }
+    // One cell forward, y grows downwards
+    fn offset(self) -> IVec2 {
+        match self {
+            Heading::Up => IVec2::NEG_Y,
+            Heading::Right => IVec2::X,
+            Heading::Down => IVec2::Y,
+            Heading::Left => IVec2::NEG_X,
+        }
+    }
+}
+impl Turn {
+    // Golly's numbers for the turns
+    fn from_code(code: u32) -> Option<Turn> {
+        match code {
+            1 => Some(Turn::None),
+            2 => Some(Turn::Right),
+            4 => Some(Turn::Back),
+            8 => Some(Turn::Left),
+            _ => None,
+        }
+    }
+    // The letter of the turn in the tables of ants like RL
+    pub fn letter(self) -> char {
+        match self {
+            Turn::None => 'N',
+            Turn::Right => 'R',
+            Turn::Back => 'U',
+            Turn::Left => 'L',
+        }
+    }
+    fn code(self) -> u32 {
+        match self {
+            Turn::None => 1,
+            Turn::Right => 2,
+            Turn::Back => 4,
+            Turn::Left => 8,
+        }
+    }
+}
+impl TurmiteTable {
+    pub fn states(&self) -> usize {
+        self.0.len()
+    }
+    pub fn colors(&self) -> usize {
+        self.0[0].len()
+    }
+    // What a turmite in `state` does on a cell in state `color`. Colors the table doesn't have are read as
+    // the ones it has, cycling
+    pub fn transition(&self, state: u8, color: State) -> Transition {
+        let row = &self.0[state as usize % self.states()];
+        row[color.0 as usize % row.len()]
+    }
+    // Adds or removes states and colors at the end, new transitions write the first color, don't turn and go
+    // to the first state
+    pub fn resize(&mut self, states: usize, colors: usize) {
+        let states = states.clamp(1, MAX_TURMITE_STATES);
+        let colors = colors.clamp(2, MAX_TURMITE_COLORS);
+        for row in self.0.iter_mut() {
+            row.resize_with(colors, Transition::default);
+        }
+        self.0.resize(states, vec![Transition::default(); colors]);
+        // Transitions can't lead to states or colors that are gone
+        for transition in self.0.iter_mut().flatten() {
+            transition.color = transition.color.min(colors as u8 - 1);
+            transition.state = transition.state.min(states as u8 - 1);
+        }
+    }
+    // Reads {{{1,2,0},{0,8,0}}} or RL, with N for no turn and U for a U-turn
+    pub fn parse(text: &str) -> Result<TurmiteTable, String> {
+        let text: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+        if text.is_empty() {
+            return Err(String::from("The table is empty"));
+        }
+        let table = if text.starts_with('{') {
+            parse_braces(&text)?
+        } else {
+            let turns = text
+                .chars()
+                .map(|c| match c.to_ascii_uppercase() {
+                    'N' => Ok(Turn::None),
+                    'R' => Ok(Turn::Right),
+                    'U' => Ok(Turn::Back),
+                    'L' => Ok(Turn::Left),
+                    _ => Err(format!("\"{}\" isn't a turn, they're L, R, N or U", c)),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let colors = turns.len();
+            let row = turns
+                .into_iter()
+                .enumerate()
+                .map(|(color, turn)| Transition {
+                    color: ((color + 1) % colors) as u8,
+                    turn,
+                    state: 0,
+                })
+                .collect();
+            TurmiteTable(vec![row])
+        };
+        if !(2..=MAX_TURMITE_COLORS).contains(&table.colors()) {
+            return Err(format!(
+                "Turmites have from 2 to {} colors",
+                MAX_TURMITE_COLORS
+            ));
+        }
+        if table.states() > MAX_TURMITE_STATES {
+            return Err(format!(
+                "Turmites have at most {} states",
+                MAX_TURMITE_STATES
+            ));
+        }
+        if table.0.iter().any(|row| row.len() != table.colors()) {
+            return Err(String::from(
+                "Every state needs a transition for each color",
+            ));
+        }
+        if let Some(transition) = table.0.iter().flatten().find(|transition| {
+            transition.color as usize >= table.colors()
+                || transition.state as usize >= table.states()
+        }) {
+            return Err(format!(
+                "{{{},{},{}}} goes to a color or a state the table doesn't have",
+                transition.color,
+                transition.turn.code(),
+                transition.state
+            ));
+        }
+        Ok(table)
+    }
+}
+
+// Written in Golly's notation, the way `TurmiteTable::parse` reads it
+impl fmt::Display for TurmiteTable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let states: Vec<String> = self
+            .0
+            .iter()
+            .map(|row| {
+                let transitions: Vec<String> = row
+                    .iter()
+                    .map(|t| format!("{{{},{},{}}}", t.color, t.turn.code(), t.state))
+                    .collect();
+                format!("{{{}}}", transitions.join(","))
+            })
+            .collect();
+        write!(f, "{{{}}}", states.join(","))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////
+/// SYSTEMS
+////////////////////////////////////////////////////////////////////////
+
+// Moves every turmite one cell on each time step
+fn walk(
+    mut ticks: EventReader<Tick>,
+    mut q_turmites: Query<&mut Turmite>,
+    mut universe: ResMut<Universe>,
+    settings: Res<TurmiteSettings>,
+    rule: Res<Rule>,
+) {
+    let size = IVec2::new(universe.width() as i32, universe.height() as i32);
+    for _ in ticks.iter() {
+        for mut turmite in q_turmites.iter_mut() {
+            // The board may have shrunk under it
+            let cell = wrap(turmite.cell, size);
+            let Some(color) = universe.get(cell.x, cell.y) else {
+                continue;
+            };
+            let transition = settings.table.transition(turmite.state, color);
+            let color = transition.color as u16 % rule.states();
+            universe.set(cell.x, cell.y, State(color as u8));
+            turmite.heading = turmite.heading.turned(transition.turn);
+            turmite.state = transition.state;
+            turmite.cell = wrap(cell + turmite.heading.offset(), size);
+        }
+    }
+}
+
+// Draws each turmite as an arrow in its color over its cell
+fn draw(
+    mut gizmos: Gizmos,
+    q_turmites: Query<&Turmite>,
+    board_size: Res<BoardSize>,
+    settings: Res<GameSettings>,
+) {
+    let cell_size = settings.cell_size as f32;
+    for turmite in q_turmites.iter() {
+        let centre = board_size.cell_to_world(turmite.cell, settings.cell_size);
+        // The offset is in cells with y down, the world has y up
+        let forward = turmite.heading.offset().as_vec2() * Vec2::new(1., -1.) * cell_size / 2.;
+        let side = forward.perp();
+        let tip = centre + forward;
+        gizmos.linestrip_2d(
+            [tip, centre - forward + side, centre - forward - side, tip],
+            turmite.color,
+        );
+    }
+}
+
+////////////////////////////////////////////////////////////////////////
+/// UTILS
+////////////////////////////////////////////////////////////////////////
+
+// A new turmite on `cell` in the first state heading up, in a color none of the last ones had
+pub fn spawn(commands: &mut Commands, settings: &mut TurmiteSettings, cell: IVec2) {
+    let hue = (settings.spawned as f32 * HUE_STEP) % 360.;
+    settings.spawned += 1;
+    commands.spawn(Turmite {
+        cell,
+        heading: Heading::default(),
+        state: 0,
+        color: Color::hsl(hue, 0.9, 0.6),
+    });
+}
+// The cell on the board `cell` is on once the edges are crossed
+fn wrap(cell: IVec2, size: IVec2) -> IVec2 {
+    IVec2::new(cell.x.rem_euclid(size.x), cell.y.rem_euclid(size.y))
+}
+// {{{1,2,0},{0,8,0}}}, a list of states each a list of transitions
+fn parse_braces(text: &str) -> Result<TurmiteTable, String> {
+    let invalid = || String::from("Tables look like {{{1,2,0},{0,8,0}}}");
+    let inner = text
+        .strip_prefix("{{{")
+        .and_then(|text| text.strip_suffix("}}}"))
+        .ok_or_else(invalid)?;
+    let mut table = vec![];
+    for state in inner.split("}},{{") {
+        let mut row = vec![];
+        for transition in state.split("},{") {
+            let numbers = transition
+                .split(',')
+                .map(|n| n.parse::<u32>().map_err(|_| invalid()))
+                .collect::<Result<Vec<_>, _>>()?;
+            let [color, turn, state] = numbers[..] else {
+                return Err(format!(
+                    "{{{}}} isn't a color, a turn and a state",
+                    transition
+                ));
+            };
+            row.push(Transition {
+                color: u8::try_from(color).map_err(|_| invalid())?,
+                turn: Turn::from_code(turn)
+                    .ok_or_else(|| format!("{} isn't a turn, they're 1, 2, 4 or 8", turn))?,
+                state: u8::try_from(state).map_err(|_| invalid())?,
+            });
+        }
+        table.push(row);
+    }
+    Ok(TurmiteTable(table))
+}
@@ -1,17 +1,22 @@
-use bevy::{
-    a11y::accesskit::TextSelection,
-    app::RunFixedUpdateLoop,
-    prelude::*,
-    render::extract_resource::{ExtractResource, ExtractResourcePlugin},
-    sprite::MaterialMesh2dBundle,
-    window::PrimaryWindow,
-};
+use bevy::{prelude::*, window::PrimaryWindow};
 use bevy_egui::{
-    egui::{self, Align2, ComboBox, Pos2, WidgetText},
-    EguiContexts, EguiPlugin,
+    egui::{self, Align2},
+    EguiContexts,
 };
 
-use crate::game_of_life::{GameSettings, Seed};
+use crate::board_material::CellShape;
+use crate::brush::{self, Brush, BrushMode, BrushTool, Connectivity, Stroke, Symmetry};
+use crate::camera::{self, MainCamera};
+use crate::game_of_life::{self, BoardSize, GameSettings, HoveredCell, Seed};
+use crate::grid::{self, GridSettings};
+use crate::lattice::Lattice;
+use crate::minimap;
+use crate::palette::{self, ColorMode, Palette, Themes};
+use crate::rle::{self, Pattern};
+use crate::rule_table;
+use crate::rules::{self, Rule};
+use crate::turmites::{self, Turmite, TurmiteSettings, TurmiteTable, Turn};
+use crate::universe::{Anchor, State, Universe, MAX_CELLS, MAX_SIDE};
 
 /**
  *  This plugin is responsible for the UI of the game
@@ -20,19 +25,92 @@ use crate::game_of_life::{GameSettings, Seed};
 #[derive(Resource)]
 pub struct GameOfLifeUI {
     show: bool,
+    // Shows a tooltip describing the cell under the cursor
+    inspector: bool,
+    // Size the canvas will be resized to, edited before pressing "Resize"
+    canvas_width: u32,
+    canvas_height: u32,
+    canvas_anchor: Anchor,
+    // Coordinate typed in "Go to"
+    go_to: IVec2,
+    // Name the palette is saved with
+    theme_name: String,
+    // Rule typed in, applied when it can be read
+    rule_text: String,
+    rule_error: Option<String>,
+    // The preset the rule is, looked up when the rule changes
+    rule_preset: Option<&'static str>,
+    // Golly .rule file pasted in or read from a path
+    rule_file_text: String,
+    #[cfg(not(target_arch = "wasm32"))]
+    rule_file_path: String,
+    rule_file_error: Option<String>,
+    // RLE pattern pasted in to be imported, or exported from the board
+    pattern_text: String,
+    pattern_error: Option<String>,
+    // Turmite table typed in, applied when it can be read
+    turmite_text: String,
+    turmite_error: Option<String>,
+    // The preset the turmite table is, looked up when the table changes
+    turmite_preset: Option<&'static str>,
 }
 impl Default for GameOfLifeUI {
     fn default() -> Self {
-        GameOfLifeUI { show: true }
+        let settings = GameSettings::default();
+        GameOfLifeUI {
+            show: true,
+            inspector: false,
+            canvas_width: settings.board_width,
+            canvas_height: settings.board_height,
+            canvas_anchor: Anchor::default(),
+            go_to: IVec2::ZERO,
+            theme_name: String::new(),
+            rule_text: Rule::default().to_string(),
+            rule_error: None,
+            rule_preset: None,
+            rule_file_text: String::new(),
+            #[cfg(not(target_arch = "wasm32"))]
+            rule_file_path: String::new(),
+            rule_file_error: None,
+            pattern_text: String::new(),
+            pattern_error: None,
+            turmite_text: TurmiteSettings::default().table.to_string(),
+            turmite_error: None,
+            turmite_preset: None,
+        }
     }
 }
 
 #[derive(Event)]
 pub enum UIEvent {
-    ChangeColor([u8; 4], [u8; 4]), // New alive and dead colors
+    ChangeRule(Rule),
+    PlacePattern(Pattern), // Centred on the board
+    ChangePalette(Palette),
+    SaveTheme(String), // Saves the current palette with this name
+    LoadThemes,
+    ChangeColorMode(ColorMode),
+    ChangeTrailLength(u16),
+    ChangeInterpolate(bool),
+    ChangeCellShape(CellShape, f32), // Shape and gap between cells
     ChangeSeed(Seed),
     ChangeTimestep(f32),
+    ChangeBackwards(bool),
     ChangeCellSize(u8),
+    ChangeBrushMode(BrushMode),
+    ChangeBrushState(State),
+    ChangeBrushPrecise(bool),
+    ChangeBrushTool(BrushTool),
+    ChangeBrushFilled(bool),
+    ChangeSymmetry(Symmetry),
+    ChangeSymmetryCenter(Option<IVec2>), // None mirrors around the board centre
+    ChangeConnectivity(Connectivity),
+    ChangeSprayDensity(f32),
+    ResizeCanvas(u32, u32, Anchor), // New width and height in cells, keeping the existing cells
+    ChangeGrid(GridSettings),
+    GoTo(IVec2), // Coordinate as shown on the rulers
+    ChangeTurmites(TurmiteSettings),
+    RemoveTurmites,
+    Start, // Steps a painted board for the first time
 }
 
 #[derive(Component)]
@@ -44,6 +122,7 @@ struct UI;
 impl Plugin for GameOfLifeUI {
     fn build(&self, app: &mut App) {
         app.insert_resource(GameOfLifeUI::default())
+            .insert_resource(GridSettings::default())
             .add_event::<UIEvent>()
             .add_plugins(bevy_egui::EguiPlugin)
             .add_systems(Startup, setup)
@@ -54,24 +133,30 @@ impl Plugin for GameOfLifeUI {
                     handle_events,
                     (update_fps_counter).run_if(should_update_counter(0.1)),
                     (update_ui_visibility),
+                    (minimap::update_density).run_if(should_update_counter(0.25)),
+                    minimap::update_viewport,
+                    minimap::navigate,
+                    cell_inspector,
+                    grid::draw_grid,
+                    grid::draw_rulers,
                 ),
             );
         // Update the fps counter every 1 second
     }
 }
 
-fn setup(mut commands: Commands) {
+fn setup(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
     let text_style = TextStyle {
         font_size: 26.,
         ..default()
     };
-    // UI - Add Text for fps counter
+    // UI - Add Text for fps counter and the minimap below it
     commands
         .spawn(NodeBundle {
             style: Style {
                 align_content: AlignContent::FlexStart,
-                align_items: AlignItems::Center,
-                flex_direction: FlexDirection::Row,
+                align_items: AlignItems::FlexStart,
+                flex_direction: FlexDirection::Column,
                 align_self: AlignSelf::Start,
                 ..default()
             },
@@ -82,6 +167,7 @@ fn setup(mut commands: Commands) {
             parent
                 .spawn(TextBundle::from_section("0", text_style))
                 .insert(FPSCounter);
+            minimap::spawn(parent, &mut images);
         });
 }
 
@@ -89,13 +175,39 @@ fn setup(mut commands: Commands) {
 /// SYSTEMS
 ////////////////////////////////////////////////////////////////////////
 
+#[allow(clippy::too_many_arguments)]
 fn egui_init(
     mut eguic: EguiContexts,
-    q_win: Query<&Window, With<PrimaryWindow>>,
-    ui_state: Res<GameOfLifeUI>,
-    mut settings: Res<GameSettings>,
+    mut ui_state: ResMut<GameOfLifeUI>,
+    settings: Res<GameSettings>,
+    brush: Res<Brush>,
+    board_size: Res<BoardSize>,
+    grid: Res<GridSettings>,
+    themes: Res<Themes>,
+    rule: Res<Rule>,
+    universe: Res<Universe>,
+    turmite_settings: Res<TurmiteSettings>,
+    q_turmites: Query<(), With<Turmite>>,
     mut ui_event: EventWriter<UIEvent>,
 ) {
+    // The rule can also come with an imported pattern
+    if rule.is_changed() {
+        ui_state.rule_text = rule.to_string();
+        ui_state.rule_error = None;
+        ui_state.rule_preset = rules::PRESETS
+            .iter()
+            .find(|(_, preset)| Rule::parse(preset).ok().as_ref() == Some(&*rule))
+            .map(|(name, _)| *name);
+    }
+    if turmite_settings.is_changed() {
+        ui_state.turmite_text = turmite_settings.table.to_string();
+        ui_state.turmite_preset = turmites::PRESETS
+            .iter()
+            .find(|(_, preset)| {
+                TurmiteTable::parse(preset).ok().as_ref() == Some(&turmite_settings.table)
+            })
+            .map(|(name, _)| *name);
+    }
     if ui_state.show {
         egui::Window::new("id")
             .auto_sized()
@@ -117,6 +229,21 @@ fn egui_init(
                     ui_event.send(UIEvent::ChangeTimestep(val));
                 }
 
+                // Reversible rules can undo their generations exactly
+                if rule.is_reversible() {
+                    let mut backwards = settings.backwards;
+                    ui.checkbox(&mut backwards, "Run backwards");
+                    if backwards != settings.backwards {
+                        ui_event.send(UIEvent::ChangeBackwards(backwards));
+                    }
+                }
+
+                let mut interpolate = settings.interpolate;
+                ui.checkbox(&mut interpolate, "Smooth transitions");
+                if interpolate != settings.interpolate {
+                    ui_event.send(UIEvent::ChangeInterpolate(interpolate));
+                }
+
                 // CELL SIZE
                 let mut cell_size = settings.cell_size;
                 ui.add(
@@ -129,32 +256,411 @@ fn egui_init(
                     ui_event.send(UIEvent::ChangeCellSize(cell_size))
                 }
 
-                // COLORS
-                let mut egui_alive_color: [f32; 4] = u8_255_color_to_f32_1(settings.alive_color);
+                // Shapes are only drawn once cells are a few pixels big on screen
+                let mut cell_shape = settings.cell_shape;
+                let mut cell_gap = settings.cell_gap;
+                egui::ComboBox::from_label("Cell shape")
+                    .selected_text(format!("{:?}", cell_shape))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut cell_shape, CellShape::Square, "Square");
+                        ui.selectable_value(
+                            &mut cell_shape,
+                            CellShape::RoundedSquare,
+                            "Rounded square",
+                        );
+                        ui.selectable_value(&mut cell_shape, CellShape::Circle, "Circle");
+                    });
+                ui.add(egui::Slider::new(&mut cell_gap, 0.0..=0.5).text("Gap"));
+                if cell_shape != settings.cell_shape || cell_gap != settings.cell_gap {
+                    ui_event.send(UIEvent::ChangeCellShape(cell_shape, cell_gap));
+                }
+
+                ui.checkbox(&mut ui_state.inspector, "Cell inspector (I)");
+
+                // RULE
+                egui::ComboBox::from_label("Rule")
+                    .selected_text(ui_state.rule_preset.unwrap_or("Custom"))
+                    .show_ui(ui, |ui| {
+                        for (name, preset) in rules::PRESETS {
+                            if ui.selectable_label(false, name).clicked() {
+                                let preset = Rule::parse(preset).unwrap();
+                                match check_runs(&preset, universe.width(), universe.height()) {
+                                    Ok(()) => ui_event.send(UIEvent::ChangeRule(preset)),
+                                    Err(err) => ui_state.rule_error = Some(err),
+                                }
+                            }
+                        }
+                    });
                 ui.horizontal(|ui| {
-                    ui.label("Alive color");
-                    ui.color_edit_button_rgba_unmultiplied(&mut egui_alive_color);
+                    let edit = ui.text_edit_singleline(&mut ui_state.rule_text);
+                    let entered =
+                        edit.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    if ui.button("Apply").clicked() || entered {
+                        let new_rule =
+                            parse_rule(&ui_state.rule_text, &rule).and_then(|new_rule| {
+                                check_runs(&new_rule, universe.width(), universe.height())
+                                    .map(|_| new_rule)
+                            });
+                        match new_rule {
+                            Ok(new_rule) => ui_event.send(UIEvent::ChangeRule(new_rule)),
+                            Err(err) => ui_state.rule_error = Some(err),
+                        }
+                    }
                 });
-                let mut egui_dead_color: [f32; 4] = u8_255_color_to_f32_1(settings.dead_color);
-                ui.horizontal(|ui| {
-                    ui.label("Dead color");
-                    ui.color_edit_button_rgba_unmultiplied(&mut egui_dead_color);
+                if let Some(err) = &ui_state.rule_error {
+                    ui.colored_label(egui::Color32::RED, err);
+                }
+                ui.collapsing("Rule file (.rule)", |ui| {
+                    // A file that was just opened is loaded right away
+                    #[cfg(not(target_arch = "wasm32"))]
+                    let load = ui
+                        .horizontal(|ui| {
+                            ui.text_edit_singleline(&mut ui_state.rule_file_path);
+                            if !ui.button("Open").clicked() {
+                                return false;
+                            }
+                            match std::fs::read_to_string(&ui_state.rule_file_path) {
+                                Ok(text) => {
+                                    ui_state.rule_file_text = text;
+                                    true
+                                }
+                                Err(err) => {
+                                    ui_state.rule_file_error = Some(err.to_string());
+                                    false
+                                }
+                            }
+                        })
+                        .inner;
+                    #[cfg(target_arch = "wasm32")]
+                    let load = false;
+                    ui.add(
+                        egui::TextEdit::multiline(&mut ui_state.rule_file_text)
+                            .code_editor()
+                            .desired_rows(6),
+                    );
+                    if ui.button("Load").clicked() || load {
+                        ui_state.rule_file_error = None;
+                        let table = rule_table::parse(&ui_state.rule_file_text).and_then(|table| {
+                            let table = Rule::Table(Box::new(table));
+                            check_runs(&table, universe.width(), universe.height()).map(|_| table)
+                        });
+                        match table {
+                            Ok(table) => ui_event.send(UIEvent::ChangeRule(table)),
+                            Err(err) => ui_state.rule_file_error = Some(err),
+                        }
+                    }
+                    if let Some(err) = &ui_state.rule_file_error {
+                        ui.colored_label(egui::Color32::RED, err);
+                    }
                 });
 
-                let egui_u8_alive_color = f32_1_color_to_u8_255(egui_alive_color);
-                let egui_u8_dead_color = f32_1_color_to_u8_255(egui_dead_color);
-                if (egui_u8_alive_color != settings.alive_color
-                    || egui_u8_dead_color != settings.dead_color)
-                    && egui_u8_alive_color != egui_u8_dead_color
-                {
-                    ui_event.send(UIEvent::ChangeColor(
-                        egui_u8_alive_color,
-                        egui_u8_dead_color,
+                // PATTERN
+                ui.collapsing("Pattern (RLE)", |ui| {
+                    // Picking a pattern of the library imports it
+                    let mut import = false;
+                    egui::ComboBox::from_label("Library")
+                        .selected_text("Pick a pattern")
+                        .show_ui(ui, |ui| {
+                            for (name, text) in rle::LIBRARY {
+                                if ui.selectable_label(false, name).clicked() {
+                                    ui_state.pattern_text = String::from(text);
+                                    import = true;
+                                }
+                            }
+                        });
+                    ui.add(
+                        egui::TextEdit::multiline(&mut ui_state.pattern_text)
+                            .code_editor()
+                            .desired_rows(6),
+                    );
+                    ui.horizontal(|ui| {
+                        if ui.button("Import").clicked() || import {
+                            ui_state.pattern_error = None;
+                            match import_pattern(&ui_state.pattern_text, &rule, &universe) {
+                                Ok((pattern, pattern_rule)) => {
+                                    if let Some(pattern_rule) = pattern_rule {
+                                        ui_event.send(UIEvent::ChangeRule(pattern_rule));
+                                    }
+                                    ui_event.send(UIEvent::PlacePattern(pattern));
+                                }
+                                Err(err) => ui_state.pattern_error = Some(err),
+                            }
+                        }
+                        if ui.button("Export").clicked() {
+                            ui_state.pattern_error = None;
+                            match Pattern::from_universe(&universe, rule.lattice()) {
+                                Ok(pattern) => {
+                                    ui_state.pattern_text = pattern
+                                        .map(|pattern| rle::write(&pattern, &rule))
+                                        .unwrap_or_default()
+                                }
+                                Err(err) => ui_state.pattern_error = Some(err),
+                            }
+                        }
+                        if ui.button("Copy").clicked() {
+                            let text = ui_state.pattern_text.clone();
+                            ui.output_mut(|o| o.copied_text = text);
+                        }
+                    });
+                    if let Some(err) = &ui_state.pattern_error {
+                        ui.colored_label(egui::Color32::RED, err);
+                    }
+                });
+
+                // TURMITES
+                ui.collapsing("Turmites", |ui| {
+                    let mut new_settings = turmite_settings.clone();
+                    ui.horizontal(|ui| {
+                        ui.label(format!("{} on the board", q_turmites.iter().count()));
+                        if ui.button("Remove all").clicked() {
+                            ui_event.send(UIEvent::RemoveTurmites);
+                        }
+                    });
+                    ui.checkbox(&mut new_settings.placing, "Place them with a left click");
+                    ui.checkbox(
+                        &mut new_settings.freeze_board,
+                        "Pause the rule while they walk",
+                    );
+                    egui::ComboBox::from_label("Table")
+                        .selected_text(ui_state.turmite_preset.unwrap_or("Custom"))
+                        .show_ui(ui, |ui| {
+                            for (name, preset) in turmites::PRESETS {
+                                if ui.selectable_label(false, name).clicked() {
+                                    new_settings.table = TurmiteTable::parse(preset).unwrap();
+                                }
+                            }
+                        });
+                    ui.horizontal(|ui| {
+                        let edit = ui.text_edit_singleline(&mut ui_state.turmite_text);
+                        let entered =
+                            edit.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                        if ui.button("Apply").clicked() || entered {
+                            match TurmiteTable::parse(&ui_state.turmite_text) {
+                                Ok(table) => {
+                                    new_settings.table = table;
+                                    ui_state.turmite_error = None;
+                                }
+                                Err(err) => ui_state.turmite_error = Some(err),
+                            }
+                        }
+                    });
+                    if let Some(err) = &ui_state.turmite_error {
+                        ui.colored_label(egui::Color32::RED, err);
+                    }
+                    if turmite_settings.table.colors() > rule.states() as usize {
+                        ui.colored_label(
+                            egui::Color32::RED,
+                            format!(
+                                "The rule only has {} states, the colors past them wrap around",
+                                rule.states()
+                            ),
+                        );
+                    }
+
+                    // The color written, the turn and the next state for each state and color
+                    let (mut states, mut colors) =
+                        (new_settings.table.states(), new_settings.table.colors());
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            egui::DragValue::new(&mut states)
+                                .clamp_range(1..=turmites::MAX_TURMITE_STATES)
+                                .prefix("States: "),
+                        );
+                        ui.add(
+                            egui::DragValue::new(&mut colors)
+                                .clamp_range(2..=turmites::MAX_TURMITE_COLORS)
+                                .prefix("Colors: "),
+                        );
+                    });
+                    new_settings.table.resize(states, colors);
+                    egui::ScrollArea::horizontal().show(ui, |ui| {
+                        egui::Grid::new("turmite_table").show(ui, |ui| {
+                            ui.label("");
+                            for color in 0..colors {
+                                ui.label(format!("Color {}", color));
+                            }
+                            ui.end_row();
+                            for (state, row) in new_settings.table.0.iter_mut().enumerate() {
+                                ui.label(format!("State {}", state));
+                                for (color, transition) in row.iter_mut().enumerate() {
+                                    ui.horizontal(|ui| {
+                                        ui.add(
+                                            egui::DragValue::new(&mut transition.color)
+                                                .clamp_range(0..=colors - 1),
+                                        );
+                                        egui::ComboBox::from_id_source(("turn", state, color))
+                                            .width(32.)
+                                            .selected_text(transition.turn.letter().to_string())
+                                            .show_ui(ui, |ui| {
+                                                for turn in [
+                                                    Turn::Left,
+                                                    Turn::Right,
+                                                    Turn::None,
+                                                    Turn::Back,
+                                                ] {
+                                                    ui.selectable_value(
+                                                        &mut transition.turn,
+                                                        turn,
+                                                        format!("{:?}", turn),
+                                                    );
+                                                }
+                                            });
+                                        ui.add(
+                                            egui::DragValue::new(&mut transition.state)
+                                                .clamp_range(0..=states - 1),
+                                        );
+                                    });
+                                }
+                                ui.end_row();
+                            }
+                        });
+                    });
+                    if new_settings != *turmite_settings {
+                        ui_event.send(UIEvent::ChangeTurmites(new_settings));
+                    }
+                });
+
+                // GRID
+                ui.collapsing("Grid", |ui| {
+                    let mut new_grid = *grid;
+                    ui.checkbox(&mut new_grid.show_grid, "Grid lines");
+                    ui.horizontal(|ui| {
+                        ui.label("Major line every");
+                        ui.add(
+                            egui::DragValue::new(&mut new_grid.major_every)
+                                .clamp_range(2..=1000)
+                                .suffix(" cells"),
+                        );
+                    });
+                    ui.checkbox(&mut new_grid.show_rulers, "Rulers");
+                    if new_grid != *grid {
+                        ui_event.send(UIEvent::ChangeGrid(new_grid));
+                    }
+
+                    ui.horizontal(|ui| {
+                        ui.label("Go to");
+                        ui.add(egui::DragValue::new(&mut ui_state.go_to.x).prefix("x: "));
+                        ui.add(egui::DragValue::new(&mut ui_state.go_to.y).prefix("y: "));
+                        if ui.button("Go").clicked() {
+                            ui_event.send(UIEvent::GoTo(ui_state.go_to));
+                        }
+                    });
+                });
+
+                // CANVAS
+                ui.collapsing("Canvas", |ui| {
+                    ui.label(format!(
+                        "Current size {} x {}",
+                        settings.board_width, settings.board_height
                     ));
-                };
+                    ui.horizontal(|ui| {
+                        ui.label("Size");
+                        ui.add(
+                            egui::DragValue::new(&mut ui_state.canvas_width)
+                                .clamp_range(1..=MAX_SIDE),
+                        );
+                        ui.label("x");
+                        ui.add(
+                            egui::DragValue::new(&mut ui_state.canvas_height)
+                                .clamp_range(1..=MAX_SIDE),
+                        );
+                    });
+                    let anchor = &mut ui_state.canvas_anchor;
+                    egui::ComboBox::from_label("Anchor")
+                        .selected_text(format!("{:?}", anchor))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(anchor, Anchor::TopLeft, "Top left");
+                            ui.selectable_value(anchor, Anchor::Top, "Top");
+                            ui.selectable_value(anchor, Anchor::TopRight, "Top right");
+                            ui.selectable_value(anchor, Anchor::Left, "Left");
+                            ui.selectable_value(anchor, Anchor::Center, "Center");
+                            ui.selectable_value(anchor, Anchor::Right, "Right");
+                            ui.selectable_value(anchor, Anchor::BottomLeft, "Bottom left");
+                            ui.selectable_value(anchor, Anchor::Bottom, "Bottom");
+                            ui.selectable_value(anchor, Anchor::BottomRight, "Bottom right");
+                        });
+                    let runs = check_runs(&rule, ui_state.canvas_width, ui_state.canvas_height);
+                    if let Err(err) = &runs {
+                        ui.colored_label(egui::Color32::RED, err);
+                    }
+                    if ui
+                        .add_enabled(runs.is_ok(), egui::Button::new("Resize"))
+                        .clicked()
+                    {
+                        ui_event.send(UIEvent::ResizeCanvas(
+                            ui_state.canvas_width,
+                            ui_state.canvas_height,
+                            ui_state.canvas_anchor,
+                        ));
+                    }
+                });
+
+                // COLORS
+                let mut palette = settings.palette;
+                egui::ComboBox::from_label("Theme")
+                    .selected_text(themes.name_of(&palette).unwrap_or("Custom"))
+                    .show_ui(ui, |ui| {
+                        for theme in themes.0.iter() {
+                            ui.selectable_value(&mut palette, theme.palette, &theme.name);
+                        }
+                    });
+                ui.collapsing("Palette", |ui| {
+                    for (name, color) in palette.colors_mut() {
+                        let mut egui_color: [f32; 4] = u8_255_color_to_f32_1(*color);
+                        ui.horizontal(|ui| {
+                            ui.color_edit_button_rgba_unmultiplied(&mut egui_color);
+                            ui.label(name.replace('_', " "));
+                        });
+                        *color = f32_1_color_to_u8_255(egui_color);
+                    }
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut ui_state.theme_name);
+                        if ui.button("Save theme").clicked() && !ui_state.theme_name.is_empty() {
+                            ui_event.send(UIEvent::SaveTheme(ui_state.theme_name.clone()));
+                        }
+                    });
+                    if ui.button("Reload themes").clicked() {
+                        ui_event.send(UIEvent::LoadThemes);
+                    }
+                });
+                if palette != settings.palette {
+                    ui_event.send(UIEvent::ChangePalette(palette));
+                }
+
+                let mut color_mode = settings.color_mode;
+                egui::ComboBox::from_label("Color mode")
+                    .selected_text(format!("{:?}", color_mode))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut color_mode, ColorMode::Classic, "Classic");
+                        ui.selectable_value(&mut color_mode, ColorMode::Age, "Age");
+                        ui.selectable_value(&mut color_mode, ColorMode::Neighbours, "Neighbours");
+                        ui.selectable_value(&mut color_mode, ColorMode::Dying, "Dying next");
+                        ui.selectable_value(
+                            &mut color_mode,
+                            ColorMode::BirthSurvival,
+                            "Birth / survival",
+                        );
+                    });
+                if color_mode != settings.color_mode {
+                    ui_event.send(UIEvent::ChangeColorMode(color_mode));
+                }
+                if color_mode == ColorMode::Age {
+                    let mut trail_length = settings.trail_length;
+                    ui.horizontal(|ui| {
+                        ui.label("Trail length");
+                        ui.add(
+                            egui::DragValue::new(&mut trail_length)
+                                .clamp_range(0..=palette::MAX_LEVEL)
+                                .suffix(" generations"),
+                        );
+                    });
+                    if trail_length != settings.trail_length {
+                        ui_event.send(UIEvent::ChangeTrailLength(trail_length));
+                    }
+                }
 
                 // Select with all the possible seeds
-                let mut selected = settings.seed.clone();
+                let mut selected = settings.seed;
                 egui::ComboBox::from_label("Seed")
                     .selected_text(format!("{:?}", selected))
                     .show_ui(ui, |ui| {
@@ -178,11 +684,169 @@ fn egui_init(
                             crate::game_of_life::Seed::SimkinGliderGun,
                             "Simking Glider Gun",
                         );
+                        ui.selectable_value(
+                            &mut selected,
+                            crate::game_of_life::Seed::SingleCell,
+                            "Single Cell",
+                        );
+                        ui.selectable_value(
+                            &mut selected,
+                            crate::game_of_life::Seed::Painted,
+                            "Painted",
+                        );
                     });
 
                 if settings.seed != selected {
                     ui_event.send(UIEvent::ChangeSeed(selected));
                 }
+                if settings.seed == crate::game_of_life::Seed::Painted && universe.generation() == 0
+                {
+                    ui.horizontal(|ui| {
+                        if rule.is_one_dimensional() {
+                            ui.label("Paint the first row");
+                        } else {
+                            ui.label("Paint the board");
+                        }
+                        if ui.button("Start").clicked() {
+                            ui_event.send(UIEvent::Start);
+                        }
+                    });
+                }
+
+                // BRUSH
+                let mut mode = brush.mode;
+                egui::ComboBox::from_label("Brush")
+                    .selected_text(format!("{:?}", mode))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut mode, BrushMode::Paint, "Paint");
+                        ui.selectable_value(&mut mode, BrushMode::Erase, "Erase");
+                        ui.selectable_value(&mut mode, BrushMode::Invert, "Invert");
+                    });
+                if mode != brush.mode {
+                    ui_event.send(UIEvent::ChangeBrushMode(mode));
+                }
+                // Rules with more states paint any of them
+                if rule.states() > 2 {
+                    let mut state = brush.state;
+                    egui::ComboBox::from_label("Paints")
+                        .selected_text(rule.state_name(state))
+                        .show_ui(ui, |ui| {
+                            for n in 1..rule.states() {
+                                let option = State(n as u8);
+                                ui.selectable_value(&mut state, option, rule.state_name(option));
+                            }
+                        });
+                    if state != brush.state {
+                        ui_event.send(UIEvent::ChangeBrushState(state));
+                    }
+                }
+
+                let mut tool = brush.tool;
+                egui::ComboBox::from_label("Tool")
+                    .selected_text(format!("{:?}", tool))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut tool, BrushTool::Freehand, "Freehand");
+                        ui.selectable_value(&mut tool, BrushTool::Line, "Line");
+                        ui.selectable_value(&mut tool, BrushTool::Rectangle, "Rectangle");
+                        ui.selectable_value(&mut tool, BrushTool::Ellipse, "Ellipse");
+                        ui.selectable_value(&mut tool, BrushTool::Fill, "Fill");
+                        ui.selectable_value(&mut tool, BrushTool::Spray, "Spray");
+                    });
+                if tool != brush.tool {
+                    ui_event.send(UIEvent::ChangeBrushTool(tool));
+                }
+
+                // Hexagons only touch by their sides, there's nothing to pick
+                if tool == BrushTool::Fill && board_size.lattice != Lattice::Hexagonal {
+                    let mut connectivity = brush.connectivity;
+                    ui.horizontal(|ui| {
+                        ui.label("Connectivity");
+                        // Neighbours through the sides, then through the sides and the corners
+                        let (sides, corners) = match board_size.lattice {
+                            Lattice::Triangular => ("3", "12"),
+                            _ => ("4", "8"),
+                        };
+                        ui.radio_value(&mut connectivity, Connectivity::Four, sides);
+                        ui.radio_value(&mut connectivity, Connectivity::Eight, corners);
+                    });
+                    if connectivity != brush.connectivity {
+                        ui_event.send(UIEvent::ChangeConnectivity(connectivity));
+                    }
+                }
+                if tool == BrushTool::Spray {
+                    let mut density = brush.spray_density;
+                    ui.add(egui::Slider::new(&mut density, 0.01..=1.0).text("Spray density"));
+                    if density != brush.spray_density {
+                        ui_event.send(UIEvent::ChangeSprayDensity(density));
+                    }
+                }
+
+                let mut filled = brush.filled;
+                ui.add_enabled(
+                    matches!(tool, BrushTool::Rectangle | BrushTool::Ellipse),
+                    egui::Checkbox::new(&mut filled, "Filled"),
+                );
+                if filled != brush.filled {
+                    ui_event.send(UIEvent::ChangeBrushFilled(filled));
+                }
+
+                let mut precise = brush.precise;
+                ui.checkbox(&mut precise, "Single cell");
+                if precise != brush.precise {
+                    ui_event.send(UIEvent::ChangeBrushPrecise(precise));
+                }
+
+                // SYMMETRY
+                let mut symmetry = brush.symmetry;
+                egui::ComboBox::from_label("Symmetry")
+                    .selected_text(format!("{:?}", symmetry))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut symmetry, Symmetry::None, "None");
+                        ui.selectable_value(&mut symmetry, Symmetry::Horizontal, "Horizontal");
+                        ui.selectable_value(&mut symmetry, Symmetry::Vertical, "Vertical");
+                        // The copies follow the lattice, a triangle only turns onto itself in thirds
+                        let turns = Symmetry::turns(board_size.lattice);
+                        ui.selectable_value(
+                            &mut symmetry,
+                            Symmetry::Rotational,
+                            format!("{}-fold", turns),
+                        );
+                        ui.selectable_value(
+                            &mut symmetry,
+                            Symmetry::Dihedral,
+                            format!("{}-fold", turns * 2),
+                        );
+                    });
+                if symmetry != brush.symmetry {
+                    ui_event.send(UIEvent::ChangeSymmetry(symmetry));
+                }
+
+                if symmetry != Symmetry::None {
+                    // Ctrl + click on the board also picks the centre
+                    let mut center = brush.symmetry_center;
+                    let mut board_center = center.is_none();
+                    ui.checkbox(&mut board_center, "Mirror around board centre");
+                    if board_center {
+                        center = None;
+                    } else {
+                        let mut c = center.unwrap_or(board_size.center());
+                        ui.horizontal(|ui| {
+                            ui.label("Centre");
+                            ui.add(
+                                egui::DragValue::new(&mut c.x)
+                                    .clamp_range(0..=board_size.rows.saturating_sub(1)),
+                            );
+                            ui.add(
+                                egui::DragValue::new(&mut c.y)
+                                    .clamp_range(0..=board_size.columns.saturating_sub(1)),
+                            );
+                        });
+                        center = Some(c);
+                    }
+                    if center != brush.symmetry_center {
+                        ui_event.send(UIEvent::ChangeSymmetryCenter(center));
+                    }
+                }
             });
     }
     // let a = .show(eguic.ctx_mut(), |ui| {
@@ -200,27 +864,123 @@ fn egui_init(
     // });
 }
 
+// Rules loaded from a file can't be typed in, only called by their name while they're the current rule
+fn parse_rule(text: &str, current: &Rule) -> Result<Rule, String> {
+    if text.trim() == current.to_string() {
+        return Ok(current.clone());
+    }
+    Rule::parse(text)
+}
+// Refuses a rule that can't run on a board of `width` x `height` cells, see `Universe::runs`
+fn check_runs(rule: &Rule, width: u32, height: u32) -> Result<(), String> {
+    if Universe::runs(width, height, rule) {
+        return Ok(());
+    }
+    let why = match rule.is_one_dimensional() {
+        true => "One dimensional rules fill the board",
+        false => "The rule brings empty cells to life",
+    };
+    Err(format!(
+        "{}, it runs on boards of at most {} cells",
+        why, MAX_CELLS
+    ))
+}
+// Reads a pasted pattern and the rule in its header, patterns without one use the current rule
+fn import_pattern(
+    text: &str,
+    current: &Rule,
+    universe: &Universe,
+) -> Result<(Pattern, Option<Rule>), String> {
+    let pattern = rle::parse(text)?;
+    let rule = pattern
+        .rule
+        .as_deref()
+        .map(|text| parse_rule(text, current))
+        .transpose()?;
+    if let Some(rule) = &rule {
+        check_runs(rule, universe.width(), universe.height())?;
+    }
+    let states = rule.as_ref().unwrap_or(current).states();
+    if pattern.cells.iter().any(|state| state.0 as u16 >= states) {
+        return Err(String::from("The pattern has more states than its rule"));
+    }
+    Ok((pattern, rule))
+}
+fn brush_mode_color(mode: BrushMode) -> Color {
+    match mode {
+        BrushMode::Paint => Color::WHITE,
+        BrushMode::Erase => Color::RED,
+        BrushMode::Invert => Color::YELLOW,
+    }
+}
 fn f32_1_color_to_u8_255(color: [f32; 4]) -> [u8; 4] {
-    return [
+    [
         (color[0] * 255.).round() as u8,
         (color[1] * 255.).round() as u8,
         (color[2] * 255.).round() as u8,
         (color[3] * 255.).round() as u8,
-    ];
+    ]
 }
 fn u8_255_color_to_f32_1(color: [u8; 4]) -> [f32; 4] {
-    return [
+    [
         color[0] as f32 / 255.,
         color[1] as f32 / 255.,
         color[2] as f32 / 255.,
         color[3] as f32 / 255.,
-    ];
+    ]
 }
 fn handle_events(keys: Res<Input<KeyCode>>, mut ui_state: ResMut<GameOfLifeUI>) {
     // Toggle the ui if U is pressed
     if keys.just_pressed(KeyCode::U) {
         ui_state.show = !ui_state.show
     }
+    // Toggle the cell inspector if I is pressed
+    if keys.just_pressed(KeyCode::I) {
+        ui_state.inspector = !ui_state.inspector
+    }
+}
+
+// Tooltip next to the cursor describing the cell under it
+fn cell_inspector(
+    mut eguic: EguiContexts,
+    ui_state: Res<GameOfLifeUI>,
+    hovered: Res<HoveredCell>,
+    universe: Res<Universe>,
+    rule: Res<Rule>,
+    q_win: Query<&Window, With<PrimaryWindow>>,
+) {
+    if !ui_state.inspector {
+        return;
+    }
+    let Some(cell) = hovered.0 else {
+        return;
+    };
+    let Some(info) = game_of_life::inspect(&universe, &rule, cell) else {
+        return;
+    };
+    let ctx = eguic.ctx_mut();
+    if ctx.is_pointer_over_area() {
+        return;
+    }
+    let cursor = q_win.single().cursor_position().unwrap_or_default();
+    let state = |state: State| rule.state_name(state);
+
+    egui::show_tooltip_at_pointer(ctx, egui::Id::new("cell_inspector"), |ui| {
+        ui.label(format!("Cell ({}, {})", cell.x, cell.y));
+        ui.label(format!("Pixel ({:.0}, {:.0})", cursor.x, cursor.y));
+        // The age stops counting at u16::MAX
+        if info.age == u16::MAX {
+            ui.label(format!("State: {} for ages", state(info.state)));
+        } else {
+            ui.label(format!(
+                "State: {} for {} generations",
+                state(info.state),
+                info.age
+            ));
+        }
+        ui.label(format!("Neighbours alive: {}", info.neighbours));
+        ui.label(format!("Next generation: {}", state(info.next)));
+    });
 }
 
 fn update_fps_counter(time: Res<Time>, mut counter: Query<&mut Text, With<FPSCounter>>) {
@@ -233,34 +993,135 @@ fn update_fps_counter(time: Res<Time>, mut counter: Query<&mut Text, With<FPSCou
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn update_ui_visibility(
     ui_state: Res<GameOfLifeUI>,
-    mut ui_elems: Query<&mut Visibility, With<UI>>,
+    mut ui_elems: Query<(&mut Visibility, &mut Style), With<UI>>,
+    grid: Res<GridSettings>,
     mut gizmos: Gizmos,
     q_win: Query<&Window, With<PrimaryWindow>>,
-    brush: Res<crate::game_of_life::Brush>,
+    q_camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    brush: Res<Brush>,
+    stroke: Res<Stroke>,
+    buttons: Res<Input<MouseButton>>,
     settings: Res<GameSettings>,
+    board_size: Res<BoardSize>,
 ) {
-    for mut ui_elem in ui_elems.iter_mut() {
+    for (mut ui_elem, mut style) in ui_elems.iter_mut() {
         if ui_state.show {
             *ui_elem = Visibility::Visible;
         } else {
             *ui_elem = Visibility::Hidden
         }
+        // Keep the overlay clear of the rulers
+        let offset = if grid.show_rulers {
+            Val::Px(grid::RULER_WIDTH)
+        } else {
+            Val::Px(0.)
+        };
+        if style.top != offset {
+            style.top = offset;
+            style.left = offset;
+        }
     }
 
     // Draw a gizmo on top of the cursor displaying the brush size
     if ui_state.show {
         let win = q_win.single();
-        if let Some(cursor) = win.cursor_position() {
-            let w = win.width();
-            let h = win.height();
-            let circle_pos = (cursor - Vec2::new(w / 2., h / 2.)) * Vec2::new(1., -1.);
-            gizmos.circle_2d(
-                circle_pos,
-                brush.size as f32 * settings.cell_size as f32,
-                Color::WHITE,
-            );
+        let (camera, camera_transform) = q_camera.single();
+        if let Some(cursor) = camera::cursor_to_world(win, camera, camera_transform) {
+            // Right click always erases, so show that while it's held
+            let mode = if buttons.pressed(MouseButton::Right) || stroke.erasing {
+                BrushMode::Erase
+            } else {
+                brush.mode
+            };
+            let color = brush_mode_color(mode);
+            let cell = board_size.world_to_cell(cursor, settings.cell_size);
+            let start = stroke.anchor.map(|(start, _)| start);
+
+            // The brush and its mirrored copies
+            let center = brush.symmetry_center.unwrap_or(board_size.center());
+            let lattice = board_size.lattice;
+            let starts = start.map(|start| brush::copies(start, brush.symmetry, center, lattice));
+            for (i, copy) in brush::copies(cell, brush.symmetry, center, lattice)
+                .into_iter()
+                .enumerate()
+            {
+                draw_brush(
+                    &mut gizmos,
+                    &brush,
+                    &board_size,
+                    settings.cell_size,
+                    copy,
+                    starts.as_ref().map(|starts| starts[i]),
+                    color,
+                );
+            }
+            let center = board_size.cell_to_world(center, settings.cell_size);
+            if brush.symmetry != Symmetry::None {
+                let arm = settings.cell_size as f32 * 2.;
+                gizmos.line_2d(center - Vec2::X * arm, center + Vec2::X * arm, Color::GRAY);
+                gizmos.line_2d(center - Vec2::Y * arm, center + Vec2::Y * arm, Color::GRAY);
+            }
+        }
+    }
+}
+
+// Draws the brush at `cursor` and the preview of the shape being rubber-banded from `start`
+fn draw_brush(
+    gizmos: &mut Gizmos,
+    brush: &Brush,
+    board_size: &BoardSize,
+    cell_size: u8,
+    cell: IVec2,
+    start: Option<IVec2>,
+    color: Color,
+) {
+    let cursor = board_size.cell_to_world(cell, cell_size);
+    let start = start.map(|start| board_size.cell_to_world(start, cell_size));
+    let lattice = board_size.lattice;
+    // The bucket only cares about the cell under the cursor, outline it whatever its shape
+    if brush.precise || brush.tool == BrushTool::Fill {
+        let outline = board_size.cell_outline(cell, cell_size);
+        let first = outline[0];
+        gizmos.linestrip_2d(outline.into_iter().chain([first]), color);
+    } else if lattice == Lattice::Square {
+        gizmos.circle_2d(cursor, brush.size as f32 * cell_size as f32, color);
+    } else {
+        // The sides of the stamp's cells that have no stamped cell across them
+        let radius = brush.radius();
+        for stamped in brush::stamp(cell, radius, lattice) {
+            let sides = lattice.sides(stamped);
+            if sides
+                .iter()
+                .all(|side| brush::in_stamp(*side, cell, radius, lattice))
+            {
+                continue;
+            }
+            let outline = board_size.cell_outline(stamped, cell_size);
+            for (i, side) in sides.iter().enumerate() {
+                if !brush::in_stamp(*side, cell, radius, lattice) {
+                    gizmos.line_2d(outline[i], outline[(i + 1) % outline.len()], color);
+                }
+            }
+        }
+    }
+
+    if let Some(start) = start {
+        let center = (start + cursor) / 2.;
+        let size = (cursor - start).abs();
+        match brush.tool {
+            BrushTool::Freehand | BrushTool::Fill | BrushTool::Spray => {}
+            BrushTool::Line => gizmos.line_2d(start, cursor, color),
+            BrushTool::Rectangle => gizmos.rect_2d(center, 0., size, color),
+            BrushTool::Ellipse => {
+                let points = (0..=48).map(|i| {
+                    let angle = i as f32 / 48. * std::f32::consts::TAU;
+                    center + Vec2::new(angle.cos(), angle.sin()) * size / 2.
+                });
+                gizmos.linestrip_2d(points, color);
+            }
         }
     }
 }
@@ -269,13 +1130,6 @@ fn update_ui_visibility(
 /// RUN CONDITIONS
 ////////////////////////////////////////////////////////////////////////
 
-fn should_update_visibility(ui: Res<GameOfLifeUI>) -> bool {
-    if ui.is_changed() {
-        true
-    } else {
-        false
-    }
-}
 fn should_update_counter(interval: f64) -> impl FnMut(Local<f64>, Res<Time>) -> bool {
     move |mut prev_interval: Local<f64>, time: Res<Time>| {
         if *prev_interval >= interval {
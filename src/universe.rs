@@ -0,0 +1,1069 @@
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+use crate::lattice;
+use crate::rule_table::MAX_INPUTS;
+use crate::rules::{Neighbourhood, Rule};
+
+/**
+ *  The state of every cell of the board. The texture of the board is only a picture of it,
+ *  so that the colours can change without changing the cells.
+ *  The board is split in chunks of `CHUNK_SIZE` x `CHUNK_SIZE` cells and only the chunks holding cells that aren't
+ *  dead, or died recently, are stored and stepped. Boards go up to `MAX_SIDE` cells on a side, 100 000 x 100 000
+ *  fits as long as most of it is empty. Rules that bring empty cells to life and one dimensional rules fill the
+ *  whole board, they run on boards of at most `MAX_CELLS` cells (see `Universe::runs`).
+ */
+
+// Most cells on a side of the board
+pub const MAX_SIDE: u32 = 100_000;
+// Most cells on a board every cell of which is stored, and in a pattern. With a byte for the cell, one for its
+// next state and two for its age that's 256 MiB
+pub const MAX_CELLS: usize = 1 << 26;
+// Cells per side of the chunks the board is stored in, they also track when their cells change, see
+// `Universe::last_change`
+pub const CHUNK_SIZE: i32 = 256;
+const CHUNK_CELLS: usize = (CHUNK_SIZE * CHUNK_SIZE) as usize;
+
+// 0 is dead and 1 alive, rules with more states use the rest (the dying cells of Generations rules)
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct State(pub u8);
+
+impl State {
+    pub const DEAD: State = State(0);
+    pub const ALIVE: State = State(1);
+}
+
+// Which part of the board stays in place when the canvas is resized
+#[derive(PartialEq, Debug, Copy, Clone, Default)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    #[default]
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+// When the cells of a chunk last changed
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct Change {
+    // `Universe::revision` after the change
+    pub revision: u64,
+    // `Universe::steps` when it happened, the ages of its cells count from there
+    pub step: u64,
+}
+
+// The cells of a chunk row by row. The chunks on the right and bottom of the board hang over it, their cells
+// past the edge stay dead
+#[derive(Debug, Clone)]
+struct Chunk {
+    cells: Vec<State>,
+    // Generations each cell has spent in its current state, 0 on the generation it changed
+    ages: Vec<u16>,
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct Universe {
+    width: u32,
+    height: u32,
+    // Row by row, the chunks that aren't stored only have cells that were never alive or died long ago
+    chunks: Vec<Option<Box<Chunk>>>,
+    generation: u64,
+    // Row of the last generation of a one dimensional rule, the next one goes under it
+    front: u32,
+    // Goes up with every change to the cells, it keeps going up when the universe is cleared or resized
+    revision: u64,
+    // Steps taken forward or back, unlike the generation it never goes down
+    steps: u64,
+    // The last change of each chunk, row by row
+    changes: Vec<Change>,
+}
+
+impl Anchor {
+    // Fraction of the added (or removed) width and height that goes before the existing cells
+    fn weights(&self) -> Vec2 {
+        match self {
+            Anchor::TopLeft => Vec2::new(0., 0.),
+            Anchor::Top => Vec2::new(0.5, 0.),
+            Anchor::TopRight => Vec2::new(1., 0.),
+            Anchor::Left => Vec2::new(0., 0.5),
+            Anchor::Center => Vec2::new(0.5, 0.5),
+            Anchor::Right => Vec2::new(1., 0.5),
+            Anchor::BottomLeft => Vec2::new(0., 1.),
+            Anchor::Bottom => Vec2::new(0.5, 1.),
+            Anchor::BottomRight => Vec2::new(1., 1.),
+        }
+    }
+}
+
+impl Chunk {
+    fn new() -> Self {
+        Chunk {
+            cells: vec![State::DEAD; CHUNK_CELLS],
+            // Cells that were never alive count as long dead
+            ages: vec![u16::MAX; CHUNK_CELLS],
+        }
+    }
+    // Whether any cell isn't dead
+    fn is_awake(&self) -> bool {
+        self.cells.iter().any(|cell| *cell != State::DEAD)
+    }
+    // Whether it's the same as a chunk that isn't stored
+    fn is_empty(&self) -> bool {
+        !self.is_awake() && self.ages.iter().all(|age| *age == u16::MAX)
+    }
+}
+
+impl Universe {
+    // Panics when the board doesn't fit, see `Universe::fits`
+    pub fn new(width: u32, height: u32) -> Self {
+        assert!(
+            Universe::fits(width, height),
+            "A board of {} x {} cells is too big",
+            width,
+            height
+        );
+        let len = chunks(width) * chunks(height);
+        Universe {
+            width,
+            height,
+            chunks: vec![None; len],
+            generation: 0,
+            front: 0,
+            revision: 0,
+            steps: 0,
+            changes: vec![Change::default(); len],
+        }
+    }
+    // Whether a board of `width` x `height` cells can be stored
+    pub fn fits(width: u32, height: u32) -> bool {
+        width <= MAX_SIDE && height <= MAX_SIDE
+    }
+    // Whether `rule` can run on a board of `width` x `height` cells. Rules that bring empty cells to life and the
+    // space-time diagrams of one dimensional rules fill the board, so every cell of it is stored
+    pub fn runs(width: u32, height: u32, rule: &Rule) -> bool {
+        let fills = rule.wakes_empty() || rule.is_one_dimensional();
+        Universe::fits(width, height) && (!fills || width as usize * height as usize <= MAX_CELLS)
+    }
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+    // Generations stepped since the universe was created
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+    // The latest change to the chunks holding the cells from `min` to `max` included
+    pub fn last_change(&self, min: IVec2, max: IVec2) -> Change {
+        let row = chunks(self.width) as i32;
+        let last = IVec2::new(row, chunks(self.height) as i32) - 1;
+        let min = (min / CHUNK_SIZE).clamp(IVec2::ZERO, last);
+        let max = (max / CHUNK_SIZE).clamp(IVec2::ZERO, last);
+        let mut latest = Change::default();
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                let change = self.changes[(y * row + x) as usize];
+                if change.revision > latest.revision {
+                    latest = change;
+                }
+            }
+        }
+        latest
+    }
+    // The chunks that changed after `revision`, by their index
+    pub fn changed_chunks(&self, revision: u64) -> impl Iterator<Item = IVec2> + '_ {
+        let row = chunks(self.width);
+        self.changes
+            .iter()
+            .enumerate()
+            .filter(move |(_, change)| change.revision > revision)
+            .map(move |(i, _)| IVec2::new((i % row) as i32, (i / row) as i32))
+    }
+    // Records a change to a chunk
+    fn changed(&mut self, chunk: usize) {
+        self.changes[chunk] = Change {
+            revision: self.revision,
+            step: self.steps,
+        };
+    }
+    // Records a change to every chunk, after the universe is cleared or resized
+    fn changed_everywhere(&mut self, revision: u64) {
+        self.revision = revision;
+        let change = Change {
+            revision,
+            step: self.steps,
+        };
+        self.changes.fill(change);
+    }
+    // The chunk holding a cell of the board and the index of the cell in the chunk
+    fn locate(&self, x: i32, y: i32) -> Option<(usize, usize)> {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return None;
+        }
+        let chunk = (y / CHUNK_SIZE) as usize * chunks(self.width) + (x / CHUNK_SIZE) as usize;
+        Some((
+            chunk,
+            (y % CHUNK_SIZE * CHUNK_SIZE + x % CHUNK_SIZE) as usize,
+        ))
+    }
+    // The first cell of a chunk
+    fn origin(&self, chunk: usize) -> IVec2 {
+        let row = chunks(self.width);
+        IVec2::new((chunk % row) as i32, (chunk / row) as i32) * CHUNK_SIZE
+    }
+    // A chunk, stored first if it wasn't
+    fn chunk_mut(&mut self, chunk: usize) -> &mut Chunk {
+        self.chunks[chunk].get_or_insert_with(|| Box::new(Chunk::new()))
+    }
+    // Forgets the chunks that are the same as the ones that aren't stored
+    fn drop_empty_chunks(&mut self) {
+        for chunk in self.chunks.iter_mut() {
+            if chunk.as_ref().is_some_and(|chunk| chunk.is_empty()) {
+                *chunk = None;
+            }
+        }
+    }
+    pub fn get(&self, x: i32, y: i32) -> Option<State> {
+        let (chunk, i) = self.locate(x, y)?;
+        Some(
+            self.chunks[chunk]
+                .as_ref()
+                .map_or(State::DEAD, |chunk| chunk.cells[i]),
+        )
+    }
+    pub fn age(&self, x: i32, y: i32) -> Option<u16> {
+        let (chunk, i) = self.locate(x, y)?;
+        Some(
+            self.chunks[chunk]
+                .as_ref()
+                .map_or(u16::MAX, |chunk| chunk.ages[i]),
+        )
+    }
+    pub fn is_alive(&self, x: i32, y: i32) -> bool {
+        self.get(x, y) == Some(State::ALIVE)
+    }
+    // Sets a cell, cells outside the board are ignored
+    pub fn set(&mut self, x: i32, y: i32, state: State) {
+        let Some((chunk, i)) = self.locate(x, y) else {
+            return;
+        };
+        if self.get(x, y) != Some(state) {
+            let stored = self.chunk_mut(chunk);
+            stored.cells[i] = state;
+            stored.ages[i] = 0;
+            self.revision += 1;
+            self.changed(chunk);
+        }
+    }
+    // The cells that aren't dead and where they are, chunk by chunk
+    pub fn occupied(&self) -> impl Iterator<Item = (IVec2, State)> + '_ {
+        self.chunks
+            .iter()
+            .enumerate()
+            .filter_map(|(i, chunk)| Some((self.origin(i), chunk.as_ref()?)))
+            .flat_map(|(origin, chunk)| {
+                chunk
+                    .cells
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, state)| **state != State::DEAD)
+                    .map(move |(i, state)| {
+                        let i = i as i32;
+                        (origin + IVec2::new(i % CHUNK_SIZE, i / CHUNK_SIZE), *state)
+                    })
+            })
+    }
+    // Kills every cell and forgets their history
+    pub fn clear(&mut self) {
+        let (revision, steps) = (self.revision, self.steps);
+        *self = Universe::new(self.width, self.height);
+        self.steps = steps;
+        self.changed_everywhere(revision + 1);
+    }
+    // Kills the cells in states a rule with `states` states doesn't have
+    pub fn retain_states(&mut self, states: u16) {
+        self.revision += 1;
+        for chunk in 0..self.chunks.len() {
+            let Some(stored) = &mut self.chunks[chunk] else {
+                continue;
+            };
+            let mut changed = false;
+            for (cell, age) in stored.cells.iter_mut().zip(stored.ages.iter_mut()) {
+                if cell.0 as u16 >= states {
+                    *cell = State::DEAD;
+                    *age = 0;
+                    changed = true;
+                }
+            }
+            if changed {
+                self.changed(chunk);
+            }
+        }
+    }
+
+    // Living cells in each `block` x `block` square, row by row, with the number of squares per row and column
+    pub fn alive_per_block(&self, block: u32) -> (u32, u32, Vec<u32>) {
+        let width = self.width.div_ceil(block);
+        let height = self.height.div_ceil(block);
+        let mut alive = vec![0u32; width as usize * height as usize];
+        for (cell, state) in self.occupied() {
+            if state == State::ALIVE {
+                let (x, y) = (cell.x as u32, cell.y as u32);
+                alive[((y / block) * width + x / block) as usize] += 1;
+            }
+        }
+        (width, height, alive)
+    }
+
+    // Living cells from `min` to `max` included, the outside of the board is dead
+    pub fn alive_in(&self, min: IVec2, max: IVec2) -> u32 {
+        let min = min.max(IVec2::ZERO);
+        let max = max.min(IVec2::new(self.width as i32 - 1, self.height as i32 - 1));
+        if max.x < min.x || max.y < min.y {
+            return 0;
+        }
+        let row = chunks(self.width);
+        let mut alive = 0;
+        for chunk_y in min.y / CHUNK_SIZE..=max.y / CHUNK_SIZE {
+            for chunk_x in min.x / CHUNK_SIZE..=max.x / CHUNK_SIZE {
+                let Some(chunk) = &self.chunks[chunk_y as usize * row + chunk_x as usize] else {
+                    continue;
+                };
+                let origin = IVec2::new(chunk_x, chunk_y) * CHUNK_SIZE;
+                let from = min.max(origin) - origin;
+                let to = max.min(origin + CHUNK_SIZE - 1) - origin;
+                for y in from.y..=to.y {
+                    let row = (y * CHUNK_SIZE) as usize;
+                    alive += chunk.cells[row + from.x as usize..=row + to.x as usize]
+                        .iter()
+                        .filter(|cell| **cell == State::ALIVE)
+                        .count() as u32;
+                }
+            }
+        }
+        alive
+    }
+
+    // Counts the living cells around a cell, the outside of the board is dead
+    pub fn neighbours_alive(&self, x: i32, y: i32) -> u8 {
+        let mut neighbours_alive = 0;
+
+        // neighbours x
+        for nx in -1..=1 {
+            // neighbors y
+            for ny in -1..=1 {
+                // if its the center one (the cell we're determining)
+                if nx == 0 && ny == 0 {
+                    continue;
+                }
+                if self.is_alive(x + nx, y + ny) {
+                    neighbours_alive += 1;
+                }
+            }
+        }
+        neighbours_alive
+    }
+    // Counts the living cells in the neighbourhood of the rule around a cell, without the cell itself
+    pub fn rule_neighbours(&self, rule: &Rule, x: i32, y: i32) -> u32 {
+        let (neighbourhood, radius) = rule.neighbourhood();
+        if rule.is_one_dimensional() {
+            // The living cells above
+            return (x - radius..=x + radius)
+                .map(|x| self.is_alive(x, y - 1) as u32)
+                .sum();
+        }
+        let count = neighbourhood.count(radius, IVec2::new(x, y), |min, max| {
+            let mut alive = 0;
+            for y in min.y..=max.y {
+                for x in min.x..=max.x {
+                    alive += self.is_alive(x, y) as u32;
+                }
+            }
+            alive
+        });
+        count - self.is_alive(x, y) as u32
+    }
+    // Which cells of the 3 x 3 square around a cell are alive, row by row with the cell itself on bit 4.
+    // On the hexagonal lattice, the neighbours clockwise from the top right then the cell on bit 6
+    pub fn arrangement(&self, x: i32, y: i32, neighbourhood: Neighbourhood) -> u32 {
+        let mut arrangement = 0;
+        if neighbourhood == Neighbourhood::Hexagonal {
+            let cell = IVec2::new(x, y);
+            for (bit, neighbour) in lattice::hexagonal_neighbours(cell)
+                .iter()
+                .chain([&cell])
+                .enumerate()
+            {
+                if self.is_alive(neighbour.x, neighbour.y) {
+                    arrangement |= 1 << bit;
+                }
+            }
+            return arrangement;
+        }
+        for bit in 0..9 {
+            if self.is_alive(x + bit % 3 - 1, y + bit / 3 - 1) {
+                arrangement |= 1 << bit;
+            }
+        }
+        arrangement
+    }
+    // The states of a cell and its neighbours in the order rule tables read them, C, N, NE, E, SE, S, SW, W, NW
+    // or C, N, E, S, W. Hexagonal neighbours go clockwise from the top right like Golly's C, N, E, SE, S, W, NW
+    // on its sheared grid. The outside of the board is in state 0
+    pub fn states_around(&self, x: i32, y: i32, neighbourhood: Neighbourhood) -> [u8; MAX_INPUTS] {
+        const MOORE: [(i32, i32); 9] = [
+            (0, 0),
+            (0, -1),
+            (1, -1),
+            (1, 0),
+            (1, 1),
+            (0, 1),
+            (-1, 1),
+            (-1, 0),
+            (-1, -1),
+        ];
+        const VON_NEUMANN: [(i32, i32); 5] = [(0, 0), (0, -1), (1, 0), (0, 1), (-1, 0)];
+        let offsets: &[(i32, i32)] = match neighbourhood {
+            Neighbourhood::VonNeumann => &VON_NEUMANN,
+            _ => &MOORE,
+        };
+        let mut around = [0; MAX_INPUTS];
+        if neighbourhood == Neighbourhood::Hexagonal {
+            let cell = IVec2::new(x, y);
+            for (state, neighbour) in around
+                .iter_mut()
+                .zip([cell].iter().chain(&lattice::hexagonal_neighbours(cell)))
+            {
+                *state = self.get(neighbour.x, neighbour.y).unwrap_or_default().0;
+            }
+            return around;
+        }
+        for (state, (dx, dy)) in around.iter_mut().zip(offsets) {
+            *state = self.get(x + dx, y + dy).unwrap_or_default().0;
+        }
+        around
+    }
+    // The state of a cell on the next generation
+    pub fn next_state(&self, rule: &Rule, x: i32, y: i32) -> State {
+        let state = self.get(x, y).unwrap_or_default();
+        match rule {
+            Rule::Table(table) => State(table.next(&self.states_around(x, y, table.neighbourhood))),
+            Rule::Isotropic { neighbourhood, .. } => {
+                rule.next(state, self.arrangement(x, y, *neighbourhood))
+            }
+            // What the row above gives, wherever the last generation is
+            Rule::Wolfram { radius, .. } => {
+                let radius = *radius as i32;
+                rule.next_in_row(
+                    (x - radius..=x + radius).map(|x| self.get(x, y - 1).unwrap_or_default()),
+                )
+            }
+            Rule::Margolus { .. } => {
+                let corner = self.block_corner(x, y, self.generation);
+                if !self.holds_block(corner) {
+                    return state;
+                }
+                let next = rule.next_block(self.block(corner), false);
+                let bit = (y - corner.y) * 2 + x - corner.x;
+                State((next >> bit) & 1)
+            }
+            _ => rule.next(state, self.rule_neighbours(rule, x, y)),
+        }
+    }
+    // The top left cell of the block of the Margolus neighbourhood holding a cell. Blocks start on the first
+    // cell on even generations and one cell down and right on odd ones
+    fn block_corner(&self, x: i32, y: i32, generation: u64) -> IVec2 {
+        let offset = (generation % 2) as i32;
+        IVec2::new(
+            x - (x - offset).rem_euclid(2),
+            y - (y - offset).rem_euclid(2),
+        )
+    }
+    // Whether the whole block starting on `corner` is on the board
+    fn holds_block(&self, corner: IVec2) -> bool {
+        corner.x >= 0
+            && corner.y >= 0
+            && corner.x + 1 < self.width as i32
+            && corner.y + 1 < self.height as i32
+    }
+    // The living cells of the block starting on `corner`, see `rules::BLOCKS`
+    fn block(&self, corner: IVec2) -> u8 {
+        (0..4).fold(0, |block, bit| {
+            let alive = self.is_alive(corner.x + bit % 2, corner.y + bit / 2);
+            block | (alive as u8) << bit
+        })
+    }
+
+    // Advances the universe by one generation
+    pub fn step(&mut self, rule: &Rule) {
+        if rule.is_one_dimensional() {
+            self.step_row(rule);
+            return;
+        }
+        if let Rule::Margolus { .. } = rule {
+            self.step_blocks(rule, false);
+            return;
+        }
+        // Larger than Life neighbourhoods are counted from a summed area table of each chunk and the cells around
+        // it, so big radiuses cost the same as small ones. The other rules only look at the cells touching theirs
+        let (neighbourhood, radius) = rule.neighbourhood();
+        let mut sums: Option<(IVec2, SummedArea)> = None;
+        // Most cells of a table rule see the same states around them, they're only looked up once per step
+        let mut looked_up = HashMap::new();
+        self.step_chunks(rule, radius, |universe, origin, x, y| {
+            let state = universe.get(x, y).unwrap_or_default();
+            match rule {
+                Rule::Table(table) => {
+                    let around = universe.states_around(x, y, table.neighbourhood);
+                    State(
+                        *looked_up
+                            .entry(around)
+                            .or_insert_with(|| table.next(&around)),
+                    )
+                }
+                Rule::Isotropic { .. } => {
+                    rule.next(state, universe.arrangement(x, y, neighbourhood))
+                }
+                Rule::LargerThanLife { .. } => {
+                    if sums.as_ref().map(|(chunk, _)| *chunk) != Some(origin) {
+                        let (min, max) = (origin - radius, origin + CHUNK_SIZE - 1 + radius);
+                        sums = Some((origin, SummedArea::new(universe, min, max)));
+                    }
+                    let (_, sums) = sums.as_ref().unwrap();
+                    let count = neighbourhood
+                        .count(radius, IVec2::new(x, y), |min, max| sums.count(min, max));
+                    rule.next(state, count - (state == State::ALIVE) as u32)
+                }
+                _ => rule.next(state, universe.rule_neighbours(rule, x, y)),
+            }
+        });
+        self.generation += 1;
+    }
+    // Goes back one generation with a reversible rule, the cells are exactly the ones it had. The universe
+    // doesn't go back past the generation it started from
+    pub fn step_back(&mut self, rule: &Rule) {
+        if !rule.is_reversible() || self.generation == 0 {
+            return;
+        }
+        self.step_blocks(rule, true);
+    }
+
+    // Replaces each block of the Margolus neighbourhood by the block the rule gives, or `backwards` by the
+    // one it came from. Blocks hanging over the edge of the board stay as they are so no cell is lost
+    fn step_blocks(&mut self, rule: &Rule, backwards: bool) {
+        // Going back undoes the step from the generation before, on its blocks
+        let generation = match backwards {
+            true => self.generation - 1,
+            false => self.generation,
+        };
+        // Each cell takes its bit of the block it's in
+        self.step_chunks(rule, 1, |universe, _, x, y| {
+            let corner = universe.block_corner(x, y, generation);
+            if !universe.holds_block(corner) {
+                return universe.get(x, y).unwrap_or_default();
+            }
+            let next = rule.next_block(universe.block(corner), backwards);
+            let bit = (y - corner.y) * 2 + x - corner.x;
+            State((next >> bit) & 1)
+        });
+        self.generation = match backwards {
+            true => generation,
+            false => generation + 1,
+        };
+    }
+    // Computes the next generation of the chunks whose cells can change, cell by cell with `next_state` given the
+    // first cell of the chunk and the cell. Unless the rule brings empty cells to life, only the chunks
+    // within `radius` cells of a cell that isn't dead can change
+    fn step_chunks(
+        &mut self,
+        rule: &Rule,
+        radius: i32,
+        mut next_state: impl FnMut(&Universe, IVec2, i32, i32) -> State,
+    ) {
+        let size = IVec2::new(self.width as i32, self.height as i32);
+        let mut next = vec![];
+        for chunk in self.active_chunks(rule, radius) {
+            let origin = self.origin(chunk);
+            let end = (origin + CHUNK_SIZE).min(size);
+            let mut cells = vec![State::DEAD; CHUNK_CELLS];
+            for y in origin.y..end.y {
+                for x in origin.x..end.x {
+                    let i = ((y - origin.y) * CHUNK_SIZE + x - origin.x) as usize;
+                    cells[i] = next_state(self, origin, x, y);
+                }
+            }
+            next.push((chunk, cells));
+        }
+        self.swap_next(next);
+    }
+    // The chunks `step_chunks` steps
+    fn active_chunks(&self, rule: &Rule, radius: i32) -> Vec<usize> {
+        if rule.wakes_empty() {
+            return (0..self.chunks.len()).collect();
+        }
+        let (row, rows) = (chunks(self.width) as i32, chunks(self.height) as i32);
+        // Triangles reach one cell further along their row
+        let reach = (radius + CHUNK_SIZE) / CHUNK_SIZE;
+        let mut active = vec![false; self.chunks.len()];
+        for (i, chunk) in self.chunks.iter().enumerate() {
+            if !chunk.as_ref().is_some_and(|chunk| chunk.is_awake()) {
+                continue;
+            }
+            let (x, y) = (i as i32 % row, i as i32 / row);
+            for y in (y - reach).max(0)..=(y + reach).min(rows - 1) {
+                for x in (x - reach).max(0)..=(x + reach).min(row - 1) {
+                    active[(y * row + x) as usize] = true;
+                }
+            }
+        }
+        (0..active.len()).filter(|i| active[*i]).collect()
+    }
+    // Makes the next generation of the stepped chunks the current one, the cells that didn't change get older.
+    // The cells of the chunks that weren't stepped are dead and only get older
+    fn swap_next(&mut self, next: Vec<(usize, Vec<State>)>) {
+        self.revision += 1;
+        self.steps += 1;
+        let mut stepped = vec![false; self.chunks.len()];
+        for (chunk, cells) in next {
+            stepped[chunk] = true;
+            if self.chunks[chunk].is_none() && cells.iter().all(|cell| *cell == State::DEAD) {
+                continue;
+            }
+            let stored = self.chunk_mut(chunk);
+            let mut changed = false;
+            for ((age, old), new) in stored.ages.iter_mut().zip(&stored.cells).zip(&cells) {
+                if old == new {
+                    *age = age.saturating_add(1);
+                } else {
+                    *age = 0;
+                    changed = true;
+                }
+            }
+            stored.cells = cells;
+            if changed {
+                self.changed(chunk);
+            }
+        }
+        for (chunk, stored) in self.chunks.iter_mut().enumerate() {
+            if let (false, Some(stored)) = (stepped[chunk], stored) {
+                for age in stored.ages.iter_mut() {
+                    *age = age.saturating_add(1);
+                }
+            }
+        }
+        self.drop_empty_chunks();
+    }
+
+    // Writes the next generation of a one dimensional rule under the last one, once the bottom of the board
+    // is reached the rows scroll up and the first one is lost
+    fn step_row(&mut self, rule: &Rule) {
+        if self.height < 2 {
+            return;
+        }
+        self.steps += 1;
+        if self.front + 1 >= self.height {
+            self.scroll_up();
+            self.front = self.height - 2;
+            self.changed_everywhere(self.revision + 1);
+        } else {
+            self.revision += 1;
+        }
+        let y = self.front as i32 + 1;
+        let row: Vec<State> = (0..self.width as i32)
+            .map(|x| self.next_state(rule, x, y))
+            .collect();
+        for (x, state) in row.into_iter().enumerate() {
+            let (chunk, i) = self.locate(x as i32, y).unwrap();
+            if state == State::DEAD && self.chunks[chunk].is_none() {
+                continue;
+            }
+            let stored = self.chunk_mut(chunk);
+            stored.cells[i] = state;
+            // Cells that were never alive count as long dead
+            stored.ages[i] = if state == State::DEAD { u16::MAX } else { 0 };
+        }
+        // The whole row changed
+        for x in (0..self.width as i32).step_by(CHUNK_SIZE as usize) {
+            let (chunk, _) = self.locate(x, y).unwrap();
+            self.changed(chunk);
+        }
+        self.front += 1;
+        self.generation += 1;
+    }
+    // Moves every row one up, the first one is lost and the last one is empty
+    fn scroll_up(&mut self) {
+        let row = chunks(self.width);
+        let size = CHUNK_SIZE as usize;
+        let last = CHUNK_CELLS - size;
+        // From the top so the first row of the chunk below hasn't moved yet
+        for chunk in 0..self.chunks.len() {
+            let below = self
+                .chunks
+                .get(chunk + row)
+                .and_then(|below| below.as_ref())
+                .map(|below| (below.cells[..size].to_vec(), below.ages[..size].to_vec()));
+            if self.chunks[chunk].is_none() && below.is_none() {
+                continue;
+            }
+            let stored = self.chunk_mut(chunk);
+            stored.cells.copy_within(size.., 0);
+            stored.ages.copy_within(size.., 0);
+            match below {
+                Some((cells, ages)) => {
+                    stored.cells[last..].copy_from_slice(&cells);
+                    stored.ages[last..].copy_from_slice(&ages);
+                }
+                None => {
+                    stored.cells[last..].fill(State::DEAD);
+                    stored.ages[last..].fill(u16::MAX);
+                }
+            }
+        }
+        self.drop_empty_chunks();
+    }
+
+    // Copies the cells into a universe of `width` x `height` cells, placed according to `anchor`
+    pub fn resized(&self, width: u32, height: u32, anchor: Anchor) -> Universe {
+        let mut resized = Universe::new(width, height);
+        let old_size = Vec2::new(self.width as f32, self.height as f32);
+        let new_size = Vec2::new(width as f32, height as f32);
+        let offset = ((new_size - old_size) * anchor.weights())
+            .floor()
+            .as_ivec2();
+
+        for (chunk, stored) in self.chunks.iter().enumerate() {
+            let Some(stored) = stored else {
+                continue;
+            };
+            let origin = self.origin(chunk) + offset;
+            for (i, (state, age)) in stored.cells.iter().zip(&stored.ages).enumerate() {
+                let cell = origin + IVec2::new(i as i32 % CHUNK_SIZE, i as i32 / CHUNK_SIZE);
+                let Some((chunk, i)) = resized.locate(cell.x, cell.y) else {
+                    continue;
+                };
+                // Cells that weren't stored are the same in the new chunks
+                if *state == State::DEAD && *age == u16::MAX {
+                    continue;
+                }
+                let moved = resized.chunk_mut(chunk);
+                moved.cells[i] = *state;
+                moved.ages[i] = *age;
+            }
+        }
+        resized.generation = self.generation;
+        resized.steps = self.steps;
+        resized.changed_everywhere(self.revision + 1);
+        resized.front = (self.front as i32 + offset.y).clamp(0, height.max(1) as i32 - 1) as u32;
+        resized
+    }
+}
+
+// Chunks needed to cover `cells` cells
+fn chunks(cells: u32) -> usize {
+    cells.div_ceil(CHUNK_SIZE as u32) as usize
+}
+
+// Living cells in any rectangle of a part of the universe in constant time. Each entry is the number of living
+// cells above and to the left of a corner, with an extra row and column of zeros first
+struct SummedArea {
+    // The first cell covered
+    min: IVec2,
+    width: u32,
+    height: u32,
+    sums: Vec<u32>,
+}
+
+impl SummedArea {
+    // Covers the cells of the board from `min` to `max` included
+    fn new(universe: &Universe, min: IVec2, max: IVec2) -> Self {
+        let min = min.max(IVec2::ZERO);
+        let max = max.min(IVec2::new(universe.width as i32, universe.height as i32) - 1);
+        let size = (max - min + 1).max(IVec2::ZERO).as_uvec2();
+        let stride = (size.x + 1) as usize;
+        let mut sums = vec![0; stride * (size.y + 1) as usize];
+        for y in 0..size.y as usize {
+            let mut row = 0;
+            for x in 0..size.x as usize {
+                row += universe.is_alive(min.x + x as i32, min.y + y as i32) as u32;
+                sums[(y + 1) * stride + x + 1] = sums[y * stride + x + 1] + row;
+            }
+        }
+        SummedArea {
+            min,
+            width: size.x,
+            height: size.y,
+            sums,
+        }
+    }
+    // Living cells from `min` to `max` included, the cells it doesn't cover are dead
+    fn count(&self, min: IVec2, max: IVec2) -> u32 {
+        let (min, max) = (min - self.min, max - self.min);
+        let min = min.max(IVec2::ZERO).as_uvec2();
+        let max = max.min(IVec2::new(self.width as i32 - 1, self.height as i32 - 1));
+        if max.x < min.x as i32 || max.y < min.y as i32 {
+            return 0;
+        }
+        let max = max.as_uvec2() + 1;
+        let stride = self.width + 1;
+        let at = |x: u32, y: u32| self.sums[(y * stride + x) as usize];
+        at(max.x, max.y) + at(min.x, min.y) - at(min.x, max.y) - at(max.x, min.y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changes_are_tracked_per_chunk() {
+        let rule = Rule::default();
+        let mut universe = Universe::new(CHUNK_SIZE as u32 * 2 + 10, CHUNK_SIZE as u32);
+        let everything = (IVec2::ZERO, IVec2::new(1000, 1000));
+        assert_eq!(universe.last_change(everything.0, everything.1).revision, 0);
+
+        // A blinker in the second chunk
+        for x in 300..303 {
+            universe.set(x, 10, State::ALIVE);
+        }
+        let painted = universe.revision();
+        let first = universe.last_change(IVec2::ZERO, IVec2::splat(CHUNK_SIZE - 1));
+        let second = universe.last_change(IVec2::new(CHUNK_SIZE, 0), IVec2::new(CHUNK_SIZE, 0));
+        assert_eq!(first.revision, 0);
+        assert_eq!(second.revision, painted);
+        assert_eq!(
+            universe.changed_chunks(0).collect::<Vec<_>>(),
+            vec![IVec2::new(1, 0)]
+        );
+
+        universe.step(&rule);
+        let second = universe.last_change(IVec2::new(CHUNK_SIZE, 0), IVec2::new(CHUNK_SIZE, 0));
+        assert!(second.revision > painted);
+        assert_eq!(second.step, universe.steps());
+        assert_eq!(universe.changed_chunks(painted).count(), 1);
+
+        // Still lifes don't change their chunk
+        let mut still = Universe::new(10, 10);
+        for (x, y) in [(1, 1), (2, 1), (1, 2), (2, 2)] {
+            still.set(x, y, State::ALIVE);
+        }
+        let revision = still.revision();
+        still.step(&rule);
+        assert_eq!(still.changed_chunks(revision).count(), 0);
+
+        // Clearing changes every chunk
+        let revision = universe.revision();
+        universe.clear();
+        assert_eq!(universe.changed_chunks(revision).count(), 3);
+        assert!(universe.revision() > revision);
+    }
+
+    // A board with about a third of its cells alive, the same on every run
+    fn random(width: u32, height: u32, seed: u64) -> Universe {
+        let mut universe = Universe::new(width, height);
+        let mut seed = seed;
+        for y in 0..height as i32 {
+            for x in 0..width as i32 {
+                seed = seed
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                if (seed >> 33).is_multiple_of(3) {
+                    universe.set(x, y, State::ALIVE);
+                }
+            }
+        }
+        universe
+    }
+
+    #[test]
+    fn summed_area_counts_like_brute_force() {
+        let universe = random(23, 17, 1);
+        let sums = SummedArea::new(&universe, IVec2::splat(-10), IVec2::splat(100));
+        let brute = |min: IVec2, max: IVec2| {
+            let mut alive = 0;
+            for y in min.y..=max.y {
+                for x in min.x..=max.x {
+                    alive += universe.is_alive(x, y) as u32;
+                }
+            }
+            alive
+        };
+        // Windows inside the board, on its edges and corners, hanging over them and entirely outside
+        let starts = [-30, -5, -1, 0, 1, 7, 15, 16, 22, 23, 40];
+        for min_x in starts {
+            for min_y in starts {
+                for size in [IVec2::ZERO, IVec2::ONE, IVec2::new(4, 9), IVec2::splat(60)] {
+                    let (min, max) = (IVec2::new(min_x, min_y), IVec2::new(min_x, min_y) + size);
+                    assert_eq!(sums.count(min, max), brute(min, max), "{} {}", min, max);
+                }
+            }
+        }
+        // Empty windows
+        assert_eq!(sums.count(IVec2::new(5, 5), IVec2::new(4, 5)), 0);
+
+        // A part of the board counts the same inside it
+        let (first, last) = (IVec2::new(3, 2), IVec2::new(15, 12));
+        let part = SummedArea::new(&universe, first, last);
+        for min_y in first.y..=last.y {
+            for min_x in first.x..=last.x {
+                let min = IVec2::new(min_x, min_y);
+                for max in [min, min + IVec2::new(2, 3), last] {
+                    let max = max.min(last);
+                    assert_eq!(part.count(min, max), brute(min, max), "{} {}", min, max);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn larger_than_life_counts_like_brute_force() {
+        let universe = random(20, 20, 2);
+        for text in [
+            "R3,C0,M1,S2..3,B3..3,NM",
+            "R4,C0,M0,S2..3,B3..3,NN",
+            "R5,C0,M1,S2..3,B3..3,NC",
+        ] {
+            let rule = Rule::parse(text).unwrap();
+            let (neighbourhood, radius) = rule.neighbourhood();
+            let inside = |dx: i32, dy: i32| match neighbourhood {
+                Neighbourhood::VonNeumann => dx.abs() + dy.abs() <= radius,
+                Neighbourhood::Circular => dx * dx + dy * dy <= radius * radius + radius,
+                _ => true,
+            };
+            // Every cell including the corners and edges, whose neighbourhoods hang over the board
+            for y in 0..20 {
+                for x in 0..20 {
+                    let mut alive = 0;
+                    for dy in -radius..=radius {
+                        for dx in -radius..=radius {
+                            if (dx, dy) != (0, 0) && inside(dx, dy) {
+                                alive += universe.is_alive(x + dx, y + dy) as u32;
+                            }
+                        }
+                    }
+                    assert_eq!(
+                        universe.rule_neighbours(&rule, x, y),
+                        alive,
+                        "{} {} {}",
+                        text,
+                        x,
+                        y
+                    );
+                }
+            }
+            // Stepping counts with the summed area table
+            let mut stepped = universe.clone();
+            stepped.step(&rule);
+            for y in 0..20 {
+                for x in 0..20 {
+                    assert_eq!(stepped.get(x, y), Some(universe.next_state(&rule, x, y)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn chunks_step_like_their_cells() {
+        // Cells around the corner of four chunks, the rest of the board stays empty
+        let size = CHUNK_SIZE as u32 + 40;
+        let mut universe = Universe::new(size, size);
+        let soup = random(40, 40, 3);
+        for (cell, state) in soup.occupied() {
+            let cell = cell + CHUNK_SIZE - 20;
+            universe.set(cell.x, cell.y, state);
+        }
+        for text in ["B3/S23", "B3/S2-i34q", "B2/S34H", "R5,C0,M1,S2..3,B3..3,NC"] {
+            let rule = Rule::parse(text).unwrap();
+            let mut stepped = universe.clone();
+            stepped.step(&rule);
+            for y in 0..size as i32 {
+                for x in 0..size as i32 {
+                    let next = universe.next_state(&rule, x, y);
+                    assert_eq!(stepped.get(x, y), Some(next), "{} {} {}", text, x, y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn big_boards_only_store_the_chunks_in_use() {
+        let rule = Rule::default();
+        let mut universe = Universe::new(MAX_SIDE, MAX_SIDE);
+        // A glider going down and right from the corner of four chunks
+        let glider = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)].map(|(x, y)| IVec2::new(x, y));
+        let corner = IVec2::splat(CHUNK_SIZE * 200 - 2);
+        for cell in glider {
+            universe.set(corner.x + cell.x, corner.y + cell.y, State::ALIVE);
+        }
+        assert_eq!(universe.chunks.iter().flatten().count(), 4);
+        for _ in 0..16 {
+            universe.step(&rule);
+        }
+        let mut alive: Vec<_> = universe.occupied().map(|(cell, _)| cell).collect();
+        alive.sort_by_key(|cell| (cell.y, cell.x));
+        assert_eq!(alive, glider.map(|cell| cell + corner + 4));
+        // The chunks it left keep the ages of its dead cells, the empty chunks around it aren't stored
+        assert_eq!(universe.chunks.iter().flatten().count(), 4);
+        assert_eq!(universe.age(corner.x + 1, corner.y), Some(15));
+        assert_eq!(universe.age(corner.x - 1, corner.y), Some(u16::MAX));
+
+        // Rules that fill the board need every cell stored
+        assert!(Universe::runs(MAX_SIDE, MAX_SIDE, &rule));
+        for text in ["B0/S8", "W30"] {
+            let rule = Rule::parse(text).unwrap();
+            assert!(!Universe::runs(MAX_SIDE, MAX_SIDE, &rule), "{}", text);
+            assert!(Universe::runs(8192, 8192, &rule), "{}", text);
+        }
+    }
+
+    #[test]
+    fn block_rules_step_back_exactly() {
+        let same = |a: &Universe, b: &Universe| {
+            (0..a.height as i32).all(|y| (0..a.width as i32).all(|x| a.get(x, y) == b.get(x, y)))
+        };
+        for name in [
+            "Critters",
+            "Tron",
+            "Billiard Ball Machine",
+            "Single Rotation",
+        ] {
+            let (_, text) = crate::rules::PRESETS
+                .iter()
+                .find(|(n, _)| *n == name)
+                .unwrap();
+            let rule = Rule::parse(text).unwrap();
+            assert!(rule.is_reversible(), "{}", name);
+            // Odd sizes leave blocks hanging over the edges on every other generation
+            for (width, height) in [(40, 30), (37, 23)] {
+                let start = random(width, height, width as u64);
+                let mut universe = start.clone();
+                for _ in 0..25 {
+                    universe.step(&rule);
+                }
+                assert_eq!(universe.generation(), 25);
+                assert!(!same(&universe, &start), "{}", name);
+                for _ in 0..25 {
+                    universe.step_back(&rule);
+                }
+                assert_eq!(universe.generation(), 0);
+                assert!(same(&universe, &start), "{} on {}x{}", name, width, height);
+
+                // There's nothing before the first generation
+                universe.step_back(&rule);
+                assert_eq!(universe.generation(), 0);
+                assert!(same(&universe, &start), "{}", name);
+            }
+        }
+    }
+}